@@ -0,0 +1,161 @@
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+use crate::budget::traits::{
+    check_requested_loss, Budget, Filter, FilterStatus,
+};
+
+/// A budget for approximate (epsilon, delta) differential privacy, e.g. for
+/// truncated or Gaussian mechanisms that don't satisfy pure DP.
+///
+/// Infinite capacities can be expressed with `f64::INFINITY`, for either
+/// parameter. Requests must be finite, with a delta of at most 1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApproxDpBudget {
+    pub epsilon: f64,
+    pub delta: f64,
+}
+
+impl Budget for ApproxDpBudget {}
+
+/// A filter for approximate differential privacy, using basic composition:
+/// epsilons and deltas add up separately, and a request is rejected if either
+/// sum would exceed the capacity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApproxDpBudgetFilter {
+    pub remaining_budget: ApproxDpBudget,
+}
+
+impl Filter<ApproxDpBudget> for ApproxDpBudgetFilter {
+    type Error = anyhow::Error;
+
+    fn new(capacity: ApproxDpBudget) -> Result<Self, Self::Error> {
+        // Written to also reject NaNs.
+        if !(capacity.epsilon >= 0.0 && capacity.delta >= 0.0) {
+            bail!("Invalid approximate DP capacity {:?}", capacity);
+        }
+        let this = Self {
+            remaining_budget: capacity,
+        };
+        Ok(this)
+    }
+
+    fn can_consume(
+        &self,
+        budget: &ApproxDpBudget,
+    ) -> Result<FilterStatus, Self::Error> {
+        check_requested_loss("epsilon", budget.epsilon)?;
+        check_requested_loss("delta", budget.delta)?;
+        if budget.delta > 1.0 {
+            bail!("Requested delta must be at most 1, got {}", budget.delta);
+        }
+
+        let status = if budget.epsilon <= self.remaining_budget.epsilon
+            && budget.delta <= self.remaining_budget.delta
+        {
+            FilterStatus::Continue
+        } else {
+            FilterStatus::OutOfBudget
+        };
+        Ok(status)
+    }
+
+    fn check_and_consume(
+        &mut self,
+        budget: &ApproxDpBudget,
+    ) -> Result<FilterStatus, Self::Error> {
+        let status = self.can_consume(budget)?;
+        if status == FilterStatus::Continue {
+            let remaining = &mut self.remaining_budget;
+            // Infinite parameters stay infinite.
+            if remaining.epsilon.is_finite() {
+                remaining.epsilon -= budget.epsilon;
+            }
+            if remaining.delta.is_finite() {
+                remaining.delta -= budget.delta;
+            }
+        }
+        Ok(status)
+    }
+
+    fn get_remaining_budget(&self) -> Result<ApproxDpBudget, anyhow::Error> {
+        Ok(self.remaining_budget.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx_dp(epsilon: f64, delta: f64) -> ApproxDpBudget {
+        ApproxDpBudget { epsilon, delta }
+    }
+
+    #[test]
+    fn test_approx_dp_budget_filter() {
+        let mut filter =
+            ApproxDpBudgetFilter::new(approx_dp(1.0, 1e-6)).unwrap();
+        assert_eq!(
+            filter.check_and_consume(&approx_dp(0.5, 5e-7)).unwrap(),
+            FilterStatus::Continue
+        );
+
+        // Epsilon would go over capacity.
+        assert_eq!(
+            filter.check_and_consume(&approx_dp(0.6, 0.0)).unwrap(),
+            FilterStatus::OutOfBudget
+        );
+
+        // Delta would go over capacity.
+        assert_eq!(
+            filter.check_and_consume(&approx_dp(0.1, 6e-7)).unwrap(),
+            FilterStatus::OutOfBudget
+        );
+
+        assert_eq!(
+            filter.get_remaining_budget().unwrap(),
+            approx_dp(0.5, 1e-6 - 5e-7)
+        );
+    }
+
+    #[test]
+    fn test_infinite_approx_dp_budget_filter() {
+        assert!(ApproxDpBudgetFilter::new(approx_dp(-1.0, 0.0)).is_err());
+
+        // Infinite epsilon, finite delta.
+        let mut filter =
+            ApproxDpBudgetFilter::new(approx_dp(f64::INFINITY, 1e-6)).unwrap();
+        assert_eq!(
+            filter.check_and_consume(&approx_dp(1e6, 1e-6)).unwrap(),
+            FilterStatus::Continue
+        );
+        assert_eq!(
+            filter.check_and_consume(&approx_dp(1.0, 1e-6)).unwrap(),
+            FilterStatus::OutOfBudget
+        );
+        assert_eq!(
+            filter.get_remaining_budget().unwrap(),
+            approx_dp(f64::INFINITY, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_invalid_approx_dp_requests() {
+        let mut filter =
+            ApproxDpBudgetFilter::new(approx_dp(f64::INFINITY, 1.0)).unwrap();
+        for request in [
+            approx_dp(-1.0, 0.0),
+            approx_dp(0.0, -1e-6),
+            approx_dp(f64::NAN, 0.0),
+            approx_dp(0.0, f64::NAN),
+            approx_dp(f64::INFINITY, 0.0),
+            approx_dp(0.0, 1.5),
+        ] {
+            assert!(filter.check_and_consume(&request).is_err());
+        }
+        assert_eq!(
+            filter.get_remaining_budget().unwrap(),
+            approx_dp(f64::INFINITY, 1.0)
+        );
+    }
+}
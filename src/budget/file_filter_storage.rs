@@ -0,0 +1,264 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::budget::{
+    hashmap_filter_storage::HashMapFilterStorage,
+    traits::{Budget, Filter, FilterStatus, FilterStorage},
+};
+
+/// FilterStorage that persists filters to a local JSON file, so that consumed
+/// budget survives restarts. Filters are kept in memory with a
+/// `HashMapFilterStorage`, and written to disk after every modification.
+///
+/// Writes are crash-consistent: the new state is written to a temporary file,
+/// synced to disk and atomically renamed over the previous state before the
+/// modifying call returns. A crash can lose a deduction that is in progress,
+/// but that deduction was never acknowledged to the caller, who can thus not
+/// have released a report that depends on it. Similarly, a deduction whose
+/// write fails returns an error: it is kept in memory, but lost after a
+/// restart. Budget that was acknowledged as consumed is never refunded.
+///
+/// JSON does not support non-finite floats, so budgets that use
+/// `f64::INFINITY` (e.g. infinite RDP orders) can't be persisted.
+pub struct FileFilterStorage<K, F, B> {
+    path: PathBuf,
+    filters: HashMapFilterStorage<K, F, B>,
+}
+
+impl<K, F, B> FileFilterStorage<K, F, B>
+where
+    K: Eq + std::hash::Hash + Serialize + DeserializeOwned,
+    F: Serialize + DeserializeOwned,
+{
+    /// Opens the filter storage persisted at `path`, or creates an empty one
+    /// if the file does not exist yet. Leftovers from writes that were
+    /// interrupted by a crash are discarded.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref().to_path_buf();
+
+        let tmp_path = Self::tmp_path(&path);
+        if tmp_path.exists() {
+            fs::remove_file(&tmp_path)
+                .context("Failed to remove interrupted write")?;
+        }
+
+        let filters = if path.exists() {
+            let file = File::open(&path).context("Failed to open filters")?;
+            let entries: Vec<(K, F)> =
+                serde_json::from_reader(BufReader::new(file))
+                    .context("Failed to parse filters")?;
+            entries.into_iter().collect()
+        } else {
+            HashMapFilterStorage::new()
+        };
+
+        Ok(Self { path, filters })
+    }
+
+    fn tmp_path(path: &Path) -> PathBuf {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        PathBuf::from(tmp_path)
+    }
+
+    /// Atomically replaces the file with the current state of the filters.
+    fn persist(&self) -> Result<(), anyhow::Error> {
+        let tmp_path = Self::tmp_path(&self.path);
+        let entries: Vec<(&K, &F)> = self.filters.iter().collect();
+
+        let file = File::create(&tmp_path)
+            .context("Failed to create temporary filters file")?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, &entries)
+            .context("Failed to serialize filters")?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+
+        fs::rename(&tmp_path, &self.path)
+            .context("Failed to replace filters file")?;
+
+        // Sync the directory too, so the rename itself is durable.
+        if let Some(dir) = self.path.parent() {
+            if let Ok(dir) = File::open(dir) {
+                let _ = dir.sync_all();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<K, F, B> FilterStorage for FileFilterStorage<K, F, B>
+where
+    B: Budget,
+    F: Filter<B, Error = anyhow::Error> + Clone + Serialize + DeserializeOwned,
+    K: Eq + std::hash::Hash + Serialize + DeserializeOwned,
+{
+    type FilterId = K;
+    type Budget = B;
+    type Error = anyhow::Error;
+
+    fn new_filter(
+        &mut self,
+        filter_id: K,
+        capacity: B,
+    ) -> Result<(), Self::Error> {
+        self.filters.new_filter(filter_id, capacity)?;
+        self.persist()
+    }
+
+    fn is_initialized(
+        &mut self,
+        filter_id: &Self::FilterId,
+    ) -> Result<bool, Self::Error> {
+        self.filters.is_initialized(filter_id)
+    }
+
+    fn can_consume(
+        &self,
+        filter_id: &K,
+        budget: &B,
+    ) -> Result<FilterStatus, Self::Error> {
+        self.filters.can_consume(filter_id, budget)
+    }
+
+    /// If persisting fails, the budget stays consumed in memory but an error
+    /// is returned, so the caller must not release the report.
+    fn check_and_consume(
+        &mut self,
+        filter_id: &K,
+        budget: &B,
+    ) -> Result<FilterStatus, Self::Error> {
+        let status = self.filters.check_and_consume(filter_id, budget)?;
+        if status == FilterStatus::Continue {
+            self.persist()?;
+        }
+        Ok(status)
+    }
+
+    fn check_and_consume_all(
+        &mut self,
+        requests: &[(K, B)],
+    ) -> Result<FilterStatus, Self::Error> {
+        let status = self.filters.check_and_consume_all(requests)?;
+        if status == FilterStatus::Continue {
+            self.persist()?;
+        }
+        Ok(status)
+    }
+
+    fn get_remaining_budget(
+        &self,
+        filter_id: &Self::FilterId,
+    ) -> Result<Self::Budget, Self::Error> {
+        self.filters.get_remaining_budget(filter_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::pure_dp_filter::{PureDPBudget, PureDPBudgetFilter};
+
+    type Storage = FileFilterStorage<usize, PureDPBudgetFilter, PureDPBudget>;
+
+    #[test]
+    fn test_file_filter_storage_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("filters.json");
+
+        let mut storage = Storage::open(&path).unwrap();
+        storage.new_filter(1, PureDPBudget::Epsilon(1.0)).unwrap();
+        assert_eq!(
+            storage
+                .check_and_consume(&1, &PureDPBudget::Epsilon(0.3))
+                .unwrap(),
+            FilterStatus::Continue
+        );
+        drop(storage);
+
+        // Consumed budget survives a restart.
+        let mut storage = Storage::open(&path).unwrap();
+        assert!(storage.is_initialized(&1).unwrap());
+        assert_eq!(
+            storage.get_remaining_budget(&1).unwrap(),
+            PureDPBudget::Epsilon(0.7)
+        );
+    }
+
+    #[test]
+    fn test_file_filter_storage_crash_mid_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("filters.json");
+
+        let mut storage = Storage::open(&path).unwrap();
+        storage.new_filter(1, PureDPBudget::Epsilon(1.0)).unwrap();
+        storage
+            .check_and_consume(&1, &PureDPBudget::Epsilon(0.3))
+            .unwrap();
+        drop(storage);
+
+        // Simulate a crash in the middle of the next write: the temporary file
+        // is truncated and was never renamed.
+        fs::write(Storage::tmp_path(&path), "[[1,{\"remaining_eps").unwrap();
+
+        let mut storage = Storage::open(&path).unwrap();
+        assert_eq!(
+            storage.get_remaining_budget(&1).unwrap(),
+            PureDPBudget::Epsilon(0.7)
+        );
+        assert!(!Storage::tmp_path(&path).exists());
+
+        // The storage keeps working after recovery.
+        storage
+            .check_and_consume(&1, &PureDPBudget::Epsilon(0.2))
+            .unwrap();
+        drop(storage);
+        let storage = Storage::open(&path).unwrap();
+        assert_eq!(
+            storage.get_remaining_budget(&1).unwrap(),
+            PureDPBudget::Epsilon(0.5)
+        );
+    }
+
+    #[test]
+    fn test_file_filter_storage_failed_write_is_not_acknowledged() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("filters.json");
+
+        let mut storage = Storage::open(&path).unwrap();
+        storage.new_filter(1, PureDPBudget::Epsilon(1.0)).unwrap();
+        storage
+            .check_and_consume(&1, &PureDPBudget::Epsilon(0.3))
+            .unwrap();
+
+        // Make the next write fail, as if the device crashed before the
+        // rename.
+        fs::create_dir(Storage::tmp_path(&path)).unwrap();
+        assert!(storage
+            .check_and_consume(&1, &PureDPBudget::Epsilon(0.3))
+            .is_err());
+
+        // The failed deduction is not refunded in memory, and the caller got
+        // an error so it did not release any report.
+        assert_eq!(
+            storage.get_remaining_budget(&1).unwrap(),
+            PureDPBudget::Epsilon(0.4)
+        );
+        drop(storage);
+
+        // After a restart, the unacknowledged deduction is lost, but the
+        // acknowledged one is kept.
+        fs::remove_dir(Storage::tmp_path(&path)).unwrap();
+        let storage = Storage::open(&path).unwrap();
+        assert_eq!(
+            storage.get_remaining_budget(&1).unwrap(),
+            PureDPBudget::Epsilon(0.7)
+        );
+    }
+}
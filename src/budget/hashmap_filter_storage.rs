@@ -5,7 +5,8 @@ use anyhow::Context;
 use crate::budget::traits::{Budget, Filter, FilterStatus, FilterStorage};
 
 /// Simple implementation of FilterStorage using a HashMap.
-/// Works for any Filter that implements the Filter trait.
+/// Works for any Filter that implements the Filter trait. Filters are cloned
+/// to roll back transactions that go out of budget.
 #[derive(Debug, Default)]
 pub struct HashMapFilterStorage<K, F, Budget> {
     filters: HashMap<K, F>,
@@ -19,12 +20,28 @@ impl<K, F, Budget> HashMapFilterStorage<K, F, Budget> {
             _marker: PhantomData,
         }
     }
+
+    /// Iterates over all filters, in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &F)> {
+        self.filters.iter()
+    }
+}
+
+impl<K: Eq + std::hash::Hash, F, Budget> FromIterator<(K, F)>
+    for HashMapFilterStorage<K, F, Budget>
+{
+    fn from_iter<I: IntoIterator<Item = (K, F)>>(iter: I) -> Self {
+        Self {
+            filters: iter.into_iter().collect(),
+            _marker: PhantomData,
+        }
+    }
 }
 
 impl<K, F, B> FilterStorage for HashMapFilterStorage<K, F, B>
 where
     B: Budget,
-    F: Filter<B, Error = anyhow::Error>,
+    F: Filter<B, Error = anyhow::Error> + Clone,
     K: Eq + std::hash::Hash,
 {
     type FilterId = K;
@@ -48,6 +65,18 @@ where
         Ok(self.filters.contains_key(filter_id))
     }
 
+    fn can_consume(
+        &self,
+        filter_id: &K,
+        budget: &B,
+    ) -> Result<FilterStatus, Self::Error> {
+        let filter = self
+            .filters
+            .get(filter_id)
+            .context("Filter for epoch not initialized")?;
+        filter.can_consume(budget)
+    }
+
     fn check_and_consume(
         &mut self,
         filter_id: &K,
@@ -60,6 +89,39 @@ where
         filter.check_and_consume(budget)
     }
 
+    fn check_and_consume_all(
+        &mut self,
+        requests: &[(K, B)],
+    ) -> Result<FilterStatus, Self::Error> {
+        // Snapshot every filter involved in the transaction, once.
+        let mut snapshots: Vec<(&K, F)> = vec![];
+        for (filter_id, _) in requests {
+            if snapshots.iter().any(|(id, _)| *id == filter_id) {
+                continue;
+            }
+            let filter = self
+                .filters
+                .get(filter_id)
+                .context("Filter for epoch not initialized")?;
+            snapshots.push((filter_id, filter.clone()));
+        }
+
+        // Consume in order, and restore the snapshots as soon as one filter
+        // rejects its budget.
+        for (filter_id, budget) in requests {
+            let result = self.check_and_consume(filter_id, budget);
+            if !matches!(result, Ok(FilterStatus::Continue)) {
+                for (id, snapshot) in snapshots {
+                    if let Some(filter) = self.filters.get_mut(id) {
+                        *filter = snapshot;
+                    }
+                }
+                return result;
+            }
+        }
+        Ok(FilterStatus::Continue)
+    }
+
     fn get_remaining_budget(
         &self,
         filter_id: &Self::FilterId,
@@ -85,6 +147,12 @@ mod tests {
             PureDPBudget,
         > = HashMapFilterStorage::new();
         storage.new_filter(1, PureDPBudget::Epsilon(1.0)).unwrap();
+        assert_eq!(
+            storage
+                .can_consume(&1, &PureDPBudget::Epsilon(0.8))
+                .unwrap(),
+            FilterStatus::Continue
+        );
         assert_eq!(
             storage
                 .check_and_consume(&1, &PureDPBudget::Epsilon(0.5))
@@ -98,9 +166,87 @@ mod tests {
             FilterStatus::OutOfBudget
         );
 
+        // Checking a budget does not consume it.
+        assert_eq!(
+            storage.get_remaining_budget(&1).unwrap(),
+            PureDPBudget::Epsilon(0.5)
+        );
+
         // Filter 2 does not exist
         assert!(storage
             .check_and_consume(&3, &PureDPBudget::Epsilon(0.2))
             .is_err());
     }
+
+    #[test]
+    fn test_check_and_consume_all_rolls_back() {
+        let mut storage: HashMapFilterStorage<
+            usize,
+            PureDPBudgetFilter,
+            PureDPBudget,
+        > = HashMapFilterStorage::new();
+        storage.new_filter(1, PureDPBudget::Epsilon(1.0)).unwrap();
+        storage.new_filter(2, PureDPBudget::Epsilon(0.5)).unwrap();
+
+        // Filter 2 rejects, so filter 1 is left untouched.
+        let requests = vec![
+            (1, PureDPBudget::Epsilon(0.6)),
+            (2, PureDPBudget::Epsilon(0.6)),
+        ];
+        assert_eq!(
+            storage.check_and_consume_all(&requests).unwrap(),
+            FilterStatus::OutOfBudget
+        );
+        assert_eq!(
+            storage.get_remaining_budget(&1).unwrap(),
+            PureDPBudget::Epsilon(1.0)
+        );
+        assert_eq!(
+            storage.get_remaining_budget(&2).unwrap(),
+            PureDPBudget::Epsilon(0.5)
+        );
+
+        // Budgets for the same filter add up.
+        let requests = vec![
+            (1, PureDPBudget::Epsilon(0.6)),
+            (1, PureDPBudget::Epsilon(0.6)),
+        ];
+        assert_eq!(
+            storage.check_and_consume_all(&requests).unwrap(),
+            FilterStatus::OutOfBudget
+        );
+        assert_eq!(
+            storage.get_remaining_budget(&1).unwrap(),
+            PureDPBudget::Epsilon(1.0)
+        );
+
+        // Missing filters are an error, and leave other filters untouched.
+        let requests = vec![
+            (1, PureDPBudget::Epsilon(0.5)),
+            (3, PureDPBudget::Epsilon(0.1)),
+        ];
+        assert!(storage.check_and_consume_all(&requests).is_err());
+        assert_eq!(
+            storage.get_remaining_budget(&1).unwrap(),
+            PureDPBudget::Epsilon(1.0)
+        );
+
+        // Requests that fit in every filter are all consumed.
+        let requests = vec![
+            (1, PureDPBudget::Epsilon(0.5)),
+            (2, PureDPBudget::Epsilon(0.5)),
+        ];
+        assert_eq!(
+            storage.check_and_consume_all(&requests).unwrap(),
+            FilterStatus::Continue
+        );
+        assert_eq!(
+            storage.get_remaining_budget(&1).unwrap(),
+            PureDPBudget::Epsilon(0.5)
+        );
+        assert_eq!(
+            storage.get_remaining_budget(&2).unwrap(),
+            PureDPBudget::Epsilon(0.0)
+        );
+    }
 }
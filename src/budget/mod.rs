@@ -1,3 +1,7 @@
+pub mod approx_dp_filter;
+pub mod file_filter_storage;
 pub mod hashmap_filter_storage;
 pub mod pure_dp_filter;
+pub mod rdp_filter;
 pub mod traits;
+pub mod zcdp_filter;
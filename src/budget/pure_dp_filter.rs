@@ -1,3 +1,6 @@
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
 use crate::budget::traits::{Budget, Filter, FilterStatus};
 
 /// A simple floating-point budget for pure differential privacy, with support
@@ -5,12 +8,12 @@ use crate::budget::traits::{Budget, Filter, FilterStatus};
 ///
 /// Infinite budget can be used for noiseless testing queries and to deactivate
 /// filters by setting their capacity to `PureDPBudget::Infinite`. We use a
-/// simple f64 for epsilon and ignore floating point arithmetic issues.
+/// simple f64 for epsilon to express budgets, but filters convert it to
+/// `FixedPointEpsilon` so that floating point errors don't accumulate.
 ///
 /// TODO(https://github.com/columbia/pdslib/issues/14): use OpenDP accountant (even though it seems
-///     to also use f64) or move to a positive rational type or fixed point.
-///     We could also generalize to RDP/zCDP.
-#[derive(Debug, Clone, PartialEq)]
+///     to also use f64).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum PureDPBudget {
     /// Infinite budget, for filters with no set capacity, or requests that
     /// don't add any noise
@@ -22,45 +25,115 @@ pub enum PureDPBudget {
 
 impl Budget for PureDPBudget {}
 
+/// Number of fixed-point units in an epsilon of 1.
+const FIXED_POINT_UNITS: f64 = 1e12;
+
+/// Non-negative fixed-point epsilon, in units of 10^-12.
+///
+/// Units are far below any meaningful privacy parameter. Requested losses are
+/// rounded up and capacities down, so that tiny losses are still charged, but
+/// values within floating point error of a unit are rounded to that unit, so
+/// decimal budgets such as 0.1 or 0.0001 are represented exactly and compose
+/// without error. Supports epsilons up to ~1.8 * 10^7: larger capacities are
+/// saturated, and larger requests can't be represented.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub struct FixedPointEpsilon(u64);
+
+impl FixedPointEpsilon {
+    /// Converts a requested loss, rounding up. Returns `None` if the loss is
+    /// too large to be represented, i.e. no finite filter can afford it.
+    pub fn from_f64_up(epsilon: f64) -> Result<Option<Self>, anyhow::Error> {
+        let units = Self::to_units(epsilon, f64::ceil)?;
+        if units >= u64::MAX as f64 {
+            return Ok(None);
+        }
+        Ok(Some(Self(units as u64)))
+    }
+
+    /// Converts a capacity, rounding down. Capacities that are too large to
+    /// be represented are saturated.
+    pub fn from_f64_down(epsilon: f64) -> Result<Self, anyhow::Error> {
+        let units = Self::to_units(epsilon, f64::floor)?;
+        // Float to integer casts saturate.
+        Ok(Self(units as u64))
+    }
+
+    /// Rounded number of units in `epsilon`.
+    fn to_units(
+        epsilon: f64,
+        round: fn(f64) -> f64,
+    ) -> Result<f64, anyhow::Error> {
+        let units = epsilon * FIXED_POINT_UNITS;
+        // Only the errors of the decimal to f64 conversion and of the
+        // multiplication are rounded to the nearest unit.
+        let nearest_units = units.round();
+        let units = if (units - nearest_units).abs()
+            <= 4.0 * f64::EPSILON * units.abs()
+        {
+            nearest_units
+        } else {
+            round(units)
+        };
+        // Also rejects tiny negative epsilons that are rounded up to zero.
+        if epsilon.is_nan() || epsilon < 0.0 {
+            bail!("Epsilon {} can't be represented in fixed point", epsilon);
+        }
+        Ok(units)
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / FIXED_POINT_UNITS
+    }
+
+    /// Exact subtraction, saturating at zero.
+    pub fn saturating_sub(self, other: Self) -> Self {
+        Self(self.0.saturating_sub(other.0))
+    }
+}
+
 /// A filter for pure differential privacy.
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PureDPBudgetFilter {
-    pub remaining_budget: PureDPBudget,
+    /// Remaining epsilon, or `None` for infinite filters.
+    remaining_epsilon: Option<FixedPointEpsilon>,
 }
 
 impl Filter<PureDPBudget> for PureDPBudgetFilter {
     type Error = anyhow::Error;
 
     fn new(capacity: PureDPBudget) -> Result<Self, Self::Error> {
-        let this = Self {
-            remaining_budget: capacity,
+        let remaining_epsilon = match capacity {
+            PureDPBudget::Infinite => None,
+            PureDPBudget::Epsilon(epsilon) => {
+                Some(FixedPointEpsilon::from_f64_down(epsilon)?)
+            }
         };
+        let this = Self { remaining_epsilon };
         Ok(this)
     }
 
-    fn check_and_consume(
-        &mut self,
+    fn can_consume(
+        &self,
         budget: &PureDPBudget,
     ) -> Result<FilterStatus, Self::Error> {
-        println!("The budget that remains in this epoch is {:?}, and we need to consume this much budget {:?}", self.remaining_budget, budget);
-
-        // Check that we have enough budget and if yes, deduct in place.
-        // We check `Infinite` manually instead of implementing `PartialOrd` and
-        // `SubAssign` because we just need this in filters, not to
-        // compare or subtract arbitrary budgets.
-        let status = match self.remaining_budget {
+        // We check `Infinite` manually instead of implementing `PartialOrd`
+        // because we just need this in filters, not to compare arbitrary
+        // budgets.
+        let status = match self.remaining_epsilon {
             // Infinite filters accept all requests, even if they are infinite
             // too.
-            PureDPBudget::Infinite => FilterStatus::Continue,
-            PureDPBudget::Epsilon(remaining_epsilon) => match budget {
+            None => FilterStatus::Continue,
+            Some(remaining_epsilon) => match budget {
                 PureDPBudget::Epsilon(requested_epsilon) => {
-                    if *requested_epsilon <= remaining_epsilon {
-                        self.remaining_budget = PureDPBudget::Epsilon(
-                            remaining_epsilon - *requested_epsilon,
-                        );
-                        FilterStatus::Continue
-                    } else {
-                        FilterStatus::OutOfBudget
+                    match FixedPointEpsilon::from_f64_up(*requested_epsilon)? {
+                        Some(requested_epsilon)
+                            if requested_epsilon <= remaining_epsilon =>
+                        {
+                            FilterStatus::Continue
+                        }
+                        _ => FilterStatus::OutOfBudget,
                     }
                 }
                 // Infinite requests on finite filters are always rejected
@@ -71,8 +144,40 @@ impl Filter<PureDPBudget> for PureDPBudgetFilter {
         Ok(status)
     }
 
+    fn check_and_consume(
+        &mut self,
+        budget: &PureDPBudget,
+    ) -> Result<FilterStatus, Self::Error> {
+        println!("The budget that remains in this epoch is {:?}, and we need to consume this much budget {:?}", self.get_remaining_budget()?, budget);
+
+        // Check that we have enough budget and if yes, deduct in place.
+        let status = self.can_consume(budget)?;
+        if status == FilterStatus::Continue {
+            if let (
+                Some(remaining_epsilon),
+                PureDPBudget::Epsilon(requested_epsilon),
+            ) = (self.remaining_epsilon, budget)
+            {
+                // Accepted requests are representable.
+                if let Some(requested_epsilon) =
+                    FixedPointEpsilon::from_f64_up(*requested_epsilon)?
+                {
+                    self.remaining_epsilon = Some(
+                        remaining_epsilon.saturating_sub(requested_epsilon),
+                    );
+                }
+            }
+        }
+
+        Ok(status)
+    }
+
     fn get_remaining_budget(&self) -> Result<PureDPBudget, anyhow::Error> {
-        Ok(self.remaining_budget.clone())
+        let budget = match self.remaining_epsilon {
+            None => PureDPBudget::Infinite,
+            Some(epsilon) => PureDPBudget::Epsilon(epsilon.to_f64()),
+        };
+        Ok(budget)
     }
 }
 
@@ -84,6 +189,10 @@ mod tests {
     fn test_pure_dp_budget_filter() {
         let mut filter =
             PureDPBudgetFilter::new(PureDPBudget::Epsilon(1.0)).unwrap();
+        assert_eq!(
+            filter.can_consume(&PureDPBudget::Epsilon(1.0)).unwrap(),
+            FilterStatus::Continue
+        );
         assert_eq!(
             filter
                 .check_and_consume(&PureDPBudget::Epsilon(0.5))
@@ -96,5 +205,134 @@ mod tests {
                 .unwrap(),
             FilterStatus::OutOfBudget
         );
+        assert!(filter
+            .check_and_consume(&PureDPBudget::Epsilon(-0.1))
+            .is_err());
+    }
+
+    #[test]
+    fn test_pure_dp_budget_filter_is_exact() {
+        // With f64 arithmetic, 1.0 - 10_000 * 0.0001 is not exactly 0.
+        let mut remaining = 1.0;
+        for _ in 0..10_000 {
+            remaining -= 0.0001;
+        }
+        assert_ne!(remaining, 0.0);
+
+        let mut filter =
+            PureDPBudgetFilter::new(PureDPBudget::Epsilon(1.0)).unwrap();
+        for _ in 0..10_000 {
+            assert_eq!(
+                filter
+                    .check_and_consume(&PureDPBudget::Epsilon(0.0001))
+                    .unwrap(),
+                FilterStatus::Continue
+            );
+        }
+        assert_eq!(
+            filter.get_remaining_budget().unwrap(),
+            PureDPBudget::Epsilon(0.0)
+        );
+        assert_eq!(
+            filter
+                .check_and_consume(&PureDPBudget::Epsilon(0.0001))
+                .unwrap(),
+            FilterStatus::OutOfBudget
+        );
+        assert_eq!(
+            filter
+                .check_and_consume(&PureDPBudget::Epsilon(0.0))
+                .unwrap(),
+            FilterStatus::Continue
+        );
+    }
+
+    #[test]
+    fn test_fixed_point_epsilon() {
+        let a = FixedPointEpsilon::from_f64_down(0.3).unwrap();
+        let b = FixedPointEpsilon::from_f64_up(0.1).unwrap().unwrap();
+        assert_eq!(a.saturating_sub(b).to_f64(), 0.2);
+        assert_eq!(b.saturating_sub(a).to_f64(), 0.0);
+        assert!(FixedPointEpsilon::from_f64_up(f64::NAN).is_err());
+        assert!(FixedPointEpsilon::from_f64_up(-4e-13).is_err());
+        assert!(FixedPointEpsilon::from_f64_down(f64::NAN).is_err());
+
+        // Epsilons that are too large are saturated for capacities, and can't
+        // be represented for requests.
+        assert_eq!(FixedPointEpsilon::from_f64_down(1e8).unwrap().0, u64::MAX);
+        assert_eq!(FixedPointEpsilon::from_f64_up(1e8).unwrap(), None);
+        assert_eq!(
+            FixedPointEpsilon::from_f64_up(f64::INFINITY).unwrap(),
+            None
+        );
+
+        // Values that are not within floating point error of a unit are
+        // rounded up for requests and down for capacities.
+        assert_eq!(
+            FixedPointEpsilon::from_f64_up(4e-13).unwrap().unwrap().0,
+            1
+        );
+        assert_eq!(FixedPointEpsilon::from_f64_down(4e-13).unwrap().0, 0);
+        assert_eq!(
+            FixedPointEpsilon::from_f64_up(1.5e-12).unwrap().unwrap().0,
+            2
+        );
+        assert_eq!(FixedPointEpsilon::from_f64_down(1.5e-12).unwrap().0, 1);
+    }
+
+    #[test]
+    fn test_large_epsilons() {
+        // E.g. sensitivity 1 with a Laplace scale of 1e-9.
+        let mut filter =
+            PureDPBudgetFilter::new(PureDPBudget::Epsilon(1.0)).unwrap();
+        assert_eq!(
+            filter
+                .check_and_consume(&PureDPBudget::Epsilon(1e9))
+                .unwrap(),
+            FilterStatus::OutOfBudget
+        );
+        assert_eq!(
+            filter
+                .check_and_consume(&PureDPBudget::Epsilon(f64::INFINITY))
+                .unwrap(),
+            FilterStatus::OutOfBudget
+        );
+
+        let mut filter =
+            PureDPBudgetFilter::new(PureDPBudget::Epsilon(1e9)).unwrap();
+        assert_eq!(
+            filter
+                .check_and_consume(&PureDPBudget::Epsilon(1.0))
+                .unwrap(),
+            FilterStatus::Continue
+        );
+        assert_eq!(
+            filter
+                .check_and_consume(&PureDPBudget::Epsilon(1e9))
+                .unwrap(),
+            FilterStatus::OutOfBudget
+        );
+    }
+
+    #[test]
+    fn test_sub_unit_requests_are_charged() {
+        let mut filter =
+            PureDPBudgetFilter::new(PureDPBudget::Epsilon(1e-12)).unwrap();
+        assert_eq!(
+            filter
+                .check_and_consume(&PureDPBudget::Epsilon(4e-13))
+                .unwrap(),
+            FilterStatus::Continue
+        );
+        assert_eq!(
+            filter
+                .check_and_consume(&PureDPBudget::Epsilon(4e-13))
+                .unwrap(),
+            FilterStatus::OutOfBudget
+        );
+        assert_eq!(
+            filter.get_remaining_budget().unwrap(),
+            PureDPBudget::Epsilon(0.0)
+        );
     }
 }
@@ -0,0 +1,223 @@
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+use crate::budget::traits::{
+    check_requested_loss, Budget, Filter, FilterStatus,
+};
+
+/// A Rényi DP budget, i.e. an RDP curve evaluated on a fixed set of orders.
+///
+/// Filters and requests must use the same orders, in the same order. Infinite
+/// capacities can be expressed with `f64::INFINITY` epsilons, e.g. for filters
+/// that should accept any request. Requested epsilons must be finite.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RdpBudget {
+    /// (alpha, epsilon) pairs, where alpha > 1 is a Rényi order and epsilon is
+    /// the RDP loss at that order.
+    pub alpha_epsilons: Vec<(f64, f64)>,
+}
+
+impl Budget for RdpBudget {}
+
+/// A Rényi DP filter over multiple orders.
+///
+/// Each order behaves like an individual Rényi filter
+/// (https://arxiv.org/abs/2008.11193): consumed budgets add up and the order
+/// stays valid as long as its sum stays below capacity. The filter accepts a
+/// request as long as at least one order stays valid, since the final RDP
+/// guarantee only needs to hold for one order to be converted to (epsilon,
+/// delta)-DP. Budget is deducted on all orders, so the remaining budget can
+/// become negative for orders that are not valid anymore.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RdpBudgetFilter {
+    pub remaining_budget: RdpBudget,
+}
+
+impl Filter<RdpBudget> for RdpBudgetFilter {
+    type Error = anyhow::Error;
+
+    fn new(capacity: RdpBudget) -> Result<Self, Self::Error> {
+        for (alpha, _) in &capacity.alpha_epsilons {
+            if *alpha <= 1.0 {
+                bail!("Rényi orders must be greater than 1, got {}", alpha);
+            }
+        }
+        let this = Self {
+            remaining_budget: capacity,
+        };
+        Ok(this)
+    }
+
+    fn can_consume(
+        &self,
+        budget: &RdpBudget,
+    ) -> Result<FilterStatus, Self::Error> {
+        let remaining = &self.remaining_budget.alpha_epsilons;
+        let requested = &budget.alpha_epsilons;
+        if remaining.len() != requested.len()
+            || remaining.iter().zip(requested).any(|(r, q)| r.0 != q.0)
+        {
+            bail!(
+                "Requested RDP orders {:?} do not match the filter orders {:?}",
+                requested,
+                remaining
+            );
+        }
+        for (_, requested_epsilon) in requested {
+            check_requested_loss("RDP epsilon", *requested_epsilon)?;
+        }
+
+        let status = if remaining
+            .iter()
+            .zip(requested)
+            .any(|((_, r_eps), (_, q_eps))| q_eps <= r_eps)
+        {
+            FilterStatus::Continue
+        } else {
+            FilterStatus::OutOfBudget
+        };
+        Ok(status)
+    }
+
+    fn check_and_consume(
+        &mut self,
+        budget: &RdpBudget,
+    ) -> Result<FilterStatus, Self::Error> {
+        let status = self.can_consume(budget)?;
+        if status == FilterStatus::Continue {
+            for ((_, remaining_epsilon), (_, requested_epsilon)) in self
+                .remaining_budget
+                .alpha_epsilons
+                .iter_mut()
+                .zip(&budget.alpha_epsilons)
+            {
+                // Infinite orders stay infinite.
+                if remaining_epsilon.is_finite() {
+                    *remaining_epsilon -= requested_epsilon;
+                }
+            }
+        }
+        Ok(status)
+    }
+
+    fn get_remaining_budget(&self) -> Result<RdpBudget, anyhow::Error> {
+        Ok(self.remaining_budget.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::{
+        hashmap_filter_storage::HashMapFilterStorage, traits::FilterStorage,
+    };
+
+    fn rdp(alpha_epsilons: &[(f64, f64)]) -> RdpBudget {
+        RdpBudget {
+            alpha_epsilons: alpha_epsilons.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_rdp_budget_filter() {
+        let mut filter =
+            RdpBudgetFilter::new(rdp(&[(2.0, 1.0), (4.0, 2.0)])).unwrap();
+
+        // Both orders are still valid.
+        assert_eq!(
+            filter
+                .check_and_consume(&rdp(&[(2.0, 0.8), (4.0, 1.0)]))
+                .unwrap(),
+            FilterStatus::Continue
+        );
+
+        // Order 2 goes over capacity but order 4 is still valid.
+        assert_eq!(
+            filter
+                .check_and_consume(&rdp(&[(2.0, 0.5), (4.0, 0.5)]))
+                .unwrap(),
+            FilterStatus::Continue
+        );
+        assert_eq!(
+            filter.get_remaining_budget().unwrap(),
+            rdp(&[(2.0, 1.0 - 0.8 - 0.5), (4.0, 0.5)])
+        );
+
+        // No order can afford this request.
+        assert_eq!(
+            filter
+                .check_and_consume(&rdp(&[(2.0, 0.1), (4.0, 0.6)]))
+                .unwrap(),
+            FilterStatus::OutOfBudget
+        );
+        assert_eq!(
+            filter.get_remaining_budget().unwrap(),
+            rdp(&[(2.0, 1.0 - 0.8 - 0.5), (4.0, 0.5)])
+        );
+    }
+
+    #[test]
+    fn test_rdp_budget_filter_orders() {
+        assert!(RdpBudgetFilter::new(rdp(&[(1.0, 1.0)])).is_err());
+
+        let mut filter =
+            RdpBudgetFilter::new(rdp(&[(2.0, 1.0), (4.0, 2.0)])).unwrap();
+        assert!(filter.check_and_consume(&rdp(&[(2.0, 0.1)])).is_err());
+        assert!(filter
+            .check_and_consume(&rdp(&[(2.0, 0.1), (8.0, 0.1)]))
+            .is_err());
+    }
+
+    #[test]
+    fn test_infinite_rdp_budget_filter() {
+        let mut filter =
+            RdpBudgetFilter::new(rdp(&[(2.0, f64::INFINITY)])).unwrap();
+        assert_eq!(
+            filter.check_and_consume(&rdp(&[(2.0, 1e6)])).unwrap(),
+            FilterStatus::Continue
+        );
+        assert_eq!(
+            filter.get_remaining_budget().unwrap(),
+            rdp(&[(2.0, f64::INFINITY)])
+        );
+    }
+
+    #[test]
+    fn test_invalid_rdp_requests() {
+        let mut filter =
+            RdpBudgetFilter::new(rdp(&[(2.0, 1.0), (4.0, 2.0)])).unwrap();
+        for epsilon in [-1.0, f64::NAN, f64::INFINITY] {
+            assert!(filter
+                .check_and_consume(&rdp(&[(2.0, 0.1), (4.0, epsilon)]))
+                .is_err());
+        }
+        assert_eq!(
+            filter.get_remaining_budget().unwrap(),
+            rdp(&[(2.0, 1.0), (4.0, 2.0)])
+        );
+    }
+
+    #[test]
+    fn test_rdp_filter_storage() {
+        let mut storage: HashMapFilterStorage<
+            usize,
+            RdpBudgetFilter,
+            RdpBudget,
+        > = HashMapFilterStorage::new();
+        storage
+            .new_filter(1, rdp(&[(2.0, 1.0), (4.0, 2.0)]))
+            .unwrap();
+        assert_eq!(
+            storage
+                .check_and_consume(&1, &rdp(&[(2.0, 1.5), (4.0, 1.5)]))
+                .unwrap(),
+            FilterStatus::Continue
+        );
+        assert_eq!(
+            storage
+                .check_and_consume(&1, &rdp(&[(2.0, 0.5), (4.0, 1.0)]))
+                .unwrap(),
+            FilterStatus::OutOfBudget
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use anyhow::bail;
+
 /// Trait for privacy budgets
 pub trait Budget: Clone {
     // For now just a marker trait requiring Clone
@@ -12,6 +14,10 @@ pub trait Filter<T: Budget> {
     where
         Self: Sized;
 
+    /// Checks whether the filter could consume a given budget, without
+    /// consuming it.
+    fn can_consume(&self, budget: &T) -> Result<FilterStatus, Self::Error>;
+
     /// Tries to consume a given budget from the filter.
     /// In the formalism from https://arxiv.org/abs/1605.08294, Ok(()) corresponds to CONTINUE, and Err(FilterError::OutOfBudget) corresponds to HALT.
     fn check_and_consume(
@@ -25,6 +31,20 @@ pub trait Filter<T: Budget> {
     fn get_remaining_budget(&self) -> Result<T, Self::Error>;
 }
 
+/// Checks that a component of a requested budget, such as an epsilon or a
+/// rho, is finite and non-negative. Filters call this before comparing a
+/// request with their remaining budget, since negative requests would refund
+/// budget. Written to also reject NaNs.
+pub fn check_requested_loss(
+    name: &str,
+    requested_loss: f64,
+) -> Result<(), anyhow::Error> {
+    if !(requested_loss >= 0.0 && requested_loss.is_finite()) {
+        bail!("Invalid requested {}: {}", name, requested_loss);
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FilterStatus {
     Continue,
@@ -50,6 +70,15 @@ pub trait FilterStorage {
         filter_id: &Self::FilterId,
     ) -> Result<bool, Self::Error>;
 
+    /// Checks whether the filter with ID `filter_id` could consume a given
+    /// budget, without consuming it. Useful to deduct budget from multiple
+    /// filters only if all of them can afford it.
+    fn can_consume(
+        &self,
+        filter_id: &Self::FilterId,
+        budget: &Self::Budget,
+    ) -> Result<FilterStatus, Self::Error>;
+
     /// Tries to consume a given budget from the filter with ID `filter_id`.
     /// Returns an error if the filter does not exist, the caller can then
     /// decide to create a new filter.
@@ -59,6 +88,15 @@ pub trait FilterStorage {
         budget: &Self::Budget,
     ) -> Result<FilterStatus, Self::Error>;
 
+    /// Tries to consume budget from multiple filters as a single transaction:
+    /// either every `(filter_id, budget)` pair is consumed, or no filter is
+    /// modified at all. A filter can appear multiple times, in which case its
+    /// budgets add up. Returns an error if one of the filters does not exist.
+    fn check_and_consume_all(
+        &mut self,
+        requests: &[(Self::FilterId, Self::Budget)],
+    ) -> Result<FilterStatus, Self::Error>;
+
     /// Gets the remaining budget for a filter.
     fn get_remaining_budget(
         &self,
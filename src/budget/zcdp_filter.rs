@@ -0,0 +1,219 @@
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+use crate::budget::traits::{
+    check_requested_loss, Budget, Filter, FilterStatus,
+};
+
+/// A budget for zero-concentrated differential privacy
+/// (https://arxiv.org/abs/1605.02065), with support for infinite budget.
+///
+/// zCDP composes additively in rho, and is a tight fit for Gaussian noise: the
+/// Gaussian mechanism with L2 sensitivity `s` and standard deviation `sigma`
+/// is `s^2 / (2 sigma^2)`-zCDP.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ZcdpBudget {
+    /// Infinite budget, for filters with no set capacity, or requests that
+    /// don't add any noise
+    Infinite,
+
+    /// Finite zCDP rho
+    Rho(f64),
+}
+
+impl Budget for ZcdpBudget {}
+
+impl ZcdpBudget {
+    /// Returns the largest zCDP budget that still satisfies (epsilon,
+    /// delta)-DP, e.g. to set filter capacities from an (epsilon, delta)
+    /// target.
+    ///
+    /// Uses Prop. 1.3 from https://arxiv.org/abs/1605.02065: rho-zCDP implies
+    /// (rho + 2 sqrt(rho ln(1/delta)), delta)-DP, which we solve for rho.
+    pub fn from_approx_dp(
+        epsilon: f64,
+        delta: f64,
+    ) -> Result<Self, anyhow::Error> {
+        if epsilon.is_nan() || epsilon < 0.0 {
+            bail!("Epsilon must be non-negative, got {}", epsilon);
+        }
+        if !(delta > 0.0 && delta < 1.0) {
+            bail!("Delta must be in (0, 1), got {}", delta);
+        }
+        if epsilon.is_infinite() {
+            return Ok(ZcdpBudget::Infinite);
+        }
+
+        let log_inv_delta = (1.0 / delta).ln();
+        let sqrt_rho = (log_inv_delta + epsilon).sqrt() - log_inv_delta.sqrt();
+        Ok(ZcdpBudget::Rho(sqrt_rho * sqrt_rho))
+    }
+
+    /// Returns the epsilon such that this budget satisfies (epsilon,
+    /// delta)-DP, following the same conversion as `from_approx_dp`.
+    pub fn to_approx_dp_epsilon(
+        &self,
+        delta: f64,
+    ) -> Result<f64, anyhow::Error> {
+        if !(delta > 0.0 && delta < 1.0) {
+            bail!("Delta must be in (0, 1), got {}", delta);
+        }
+        let epsilon = match self {
+            ZcdpBudget::Infinite => f64::INFINITY,
+            ZcdpBudget::Rho(rho) => {
+                rho + 2.0 * (rho * (1.0 / delta).ln()).sqrt()
+            }
+        };
+        Ok(epsilon)
+    }
+}
+
+/// A filter for zero-concentrated differential privacy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZcdpBudgetFilter {
+    pub remaining_budget: ZcdpBudget,
+}
+
+impl Filter<ZcdpBudget> for ZcdpBudgetFilter {
+    type Error = anyhow::Error;
+
+    fn new(capacity: ZcdpBudget) -> Result<Self, Self::Error> {
+        if let ZcdpBudget::Rho(rho) = capacity {
+            if rho.is_nan() || rho < 0.0 {
+                bail!("Invalid zCDP capacity {:?}", capacity);
+            }
+        }
+        let this = Self {
+            remaining_budget: capacity,
+        };
+        Ok(this)
+    }
+
+    fn can_consume(
+        &self,
+        budget: &ZcdpBudget,
+    ) -> Result<FilterStatus, Self::Error> {
+        // Infinite requests use `ZcdpBudget::Infinite`.
+        if let ZcdpBudget::Rho(requested_rho) = budget {
+            check_requested_loss("rho", *requested_rho)?;
+        }
+
+        let status = match self.remaining_budget {
+            // Infinite filters accept all requests, even if they are infinite
+            // too.
+            ZcdpBudget::Infinite => FilterStatus::Continue,
+            ZcdpBudget::Rho(remaining_rho) => match budget {
+                ZcdpBudget::Rho(requested_rho) => {
+                    if *requested_rho <= remaining_rho {
+                        FilterStatus::Continue
+                    } else {
+                        FilterStatus::OutOfBudget
+                    }
+                }
+                // Infinite requests on finite filters are always rejected
+                _ => FilterStatus::OutOfBudget,
+            },
+        };
+
+        Ok(status)
+    }
+
+    fn check_and_consume(
+        &mut self,
+        budget: &ZcdpBudget,
+    ) -> Result<FilterStatus, Self::Error> {
+        let status = self.can_consume(budget)?;
+        if status == FilterStatus::Continue {
+            if let (
+                ZcdpBudget::Rho(remaining_rho),
+                ZcdpBudget::Rho(requested_rho),
+            ) = (&self.remaining_budget, budget)
+            {
+                self.remaining_budget =
+                    ZcdpBudget::Rho(remaining_rho - requested_rho);
+            }
+        }
+
+        Ok(status)
+    }
+
+    fn get_remaining_budget(&self) -> Result<ZcdpBudget, anyhow::Error> {
+        Ok(self.remaining_budget.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zcdp_budget_filter() {
+        let mut filter = ZcdpBudgetFilter::new(ZcdpBudget::Rho(1.0)).unwrap();
+        assert_eq!(
+            filter.check_and_consume(&ZcdpBudget::Rho(0.5)).unwrap(),
+            FilterStatus::Continue
+        );
+        assert_eq!(
+            filter.check_and_consume(&ZcdpBudget::Rho(0.6)).unwrap(),
+            FilterStatus::OutOfBudget
+        );
+        assert_eq!(
+            filter.check_and_consume(&ZcdpBudget::Infinite).unwrap(),
+            FilterStatus::OutOfBudget
+        );
+        assert_eq!(
+            filter.get_remaining_budget().unwrap(),
+            ZcdpBudget::Rho(0.5)
+        );
+
+        let mut filter = ZcdpBudgetFilter::new(ZcdpBudget::Infinite).unwrap();
+        assert_eq!(
+            filter.check_and_consume(&ZcdpBudget::Infinite).unwrap(),
+            FilterStatus::Continue
+        );
+    }
+
+    #[test]
+    fn test_invalid_zcdp_capacities() {
+        assert!(ZcdpBudgetFilter::new(ZcdpBudget::Rho(-1.0)).is_err());
+        assert!(ZcdpBudgetFilter::new(ZcdpBudget::Rho(f64::NAN)).is_err());
+        assert!(ZcdpBudgetFilter::new(ZcdpBudget::Rho(0.0)).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_zcdp_requests() {
+        let mut filter = ZcdpBudgetFilter::new(ZcdpBudget::Rho(1.0)).unwrap();
+        for rho in [-5.0, f64::NAN, f64::INFINITY] {
+            assert!(filter.check_and_consume(&ZcdpBudget::Rho(rho)).is_err());
+        }
+        assert_eq!(
+            filter.get_remaining_budget().unwrap(),
+            ZcdpBudget::Rho(1.0)
+        );
+    }
+
+    #[test]
+    fn test_zcdp_approx_dp_conversion() {
+        let delta = 1e-6;
+        let budget = ZcdpBudget::from_approx_dp(1.0, delta).unwrap();
+        let ZcdpBudget::Rho(rho) = budget else {
+            panic!("Expected a finite budget");
+        };
+        assert!(rho > 0.0 && rho < 1.0);
+
+        // Converting back gives the target epsilon.
+        let epsilon = budget.to_approx_dp_epsilon(delta).unwrap();
+        assert!((epsilon - 1.0).abs() < 1e-9);
+
+        assert_eq!(
+            ZcdpBudget::from_approx_dp(0.0, delta).unwrap(),
+            ZcdpBudget::Rho(0.0)
+        );
+        assert_eq!(
+            ZcdpBudget::from_approx_dp(f64::INFINITY, delta).unwrap(),
+            ZcdpBudget::Infinite
+        );
+        assert!(ZcdpBudget::from_approx_dp(1.0, 0.0).is_err());
+        assert!(ZcdpBudget::from_approx_dp(-1.0, delta).is_err());
+    }
+}
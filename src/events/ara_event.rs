@@ -1,16 +1,166 @@
 use std::collections::HashMap;
 
-use crate::events::traits::Event;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+use crate::events::{epoch_clock::EpochClock, traits::Event};
 
 /// Source event for ARA-style callers such as Chromium.
 /// Mimics the fields from https://source.chromium.org/chromium/chromium/src/+/main:content/browser/attribution_reporting/attribution_reporting.proto.
 ///
-/// TODO(https://github.com/columbia/pdslib/issues/8): add other fields as needed by callers, e.g. filters.
-#[derive(Debug, Clone)]
+/// TODO(https://github.com/columbia/pdslib/issues/8): add other fields as needed by callers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AraEvent {
     pub id: usize,
+
+    /// Epoch that contains `source_time`, e.g. derived with `new`.
     pub epoch_number: usize,
-    pub aggregatable_sources: HashMap<String, usize>,
+
+    /// 128-bit key pieces by aggregation key id, stored as hex strings such
+    /// as `"0x159"` like in ARA source registrations.
+    #[serde(with = "hex_key_pieces")]
+    pub aggregatable_sources: HashMap<String, u128>,
+
+    /// Filter data registered with the source, matched against the filters
+    /// of triggers. The reserved `source_type` key is populated from
+    /// `source_type` instead.
+    #[serde(default)]
+    pub filter_data: HashMap<String, Vec<String>>,
+
+    pub source_type: SourceType,
+
+    /// Source registration time, in seconds since the Unix epoch. Used for
+    /// the `_lookback_window` filter.
+    pub source_time: u64,
+
+    /// Sources with a higher priority are attributed first. Ties are broken
+    /// by recency.
+    #[serde(default)]
+    pub priority: i64,
+
+    /// Optional key set by the querier to debug attribution.
+    #[serde(default)]
+    pub debug_key: Option<u64>,
+
+    /// Number of seconds after `source_time` during which the source can
+    /// still be attributed, like ARA's source `expiry`. `None` means that the
+    /// source never expires.
+    #[serde(default)]
+    pub expiry: Option<u64>,
+}
+
+impl AraEvent {
+    /// Creates a source registered at `source_time`, in the epoch given by
+    /// `epoch_clock`, without filter data, priority, debug key or expiry.
+    pub fn new(
+        id: usize,
+        aggregatable_sources: HashMap<String, u128>,
+        source_type: SourceType,
+        source_time: u64,
+        epoch_clock: &EpochClock,
+    ) -> Self {
+        Self {
+            id,
+            epoch_number: epoch_clock.get_epoch_id(source_time),
+            aggregatable_sources,
+            filter_data: HashMap::new(),
+            source_type,
+            source_time,
+            priority: 0,
+            debug_key: None,
+            expiry: None,
+        }
+    }
+}
+
+/// Type of an ARA source, depending on how it was registered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceType {
+    /// Source registered on a navigation, e.g. a click on an ad.
+    Navigation,
+
+    /// Source registered without navigation, e.g. an ad view.
+    Event,
+}
+
+impl SourceType {
+    /// Value of the reserved `source_type` filter key.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SourceType::Navigation => "navigation",
+            SourceType::Event => "event",
+        }
+    }
+}
+
+/// Error for key pieces that are not valid ARA hex strings.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum KeyPieceError {
+    #[error("key piece {0:?} must start with \"0x\"")]
+    MissingPrefix(String),
+
+    #[error("key piece {0:?} must have between 1 and 32 hex digits")]
+    InvalidLength(String),
+
+    #[error("key piece {0:?} contains non-hex characters")]
+    InvalidDigit(String),
+}
+
+/// Parses a 128-bit key piece from a hex string such as `"0x159"`, with the
+/// same rules as Chromium: a case-insensitive `0x` prefix followed by 1 to 32
+/// hex digits.
+pub fn parse_key_piece(key_piece: &str) -> Result<u128, KeyPieceError> {
+    let digits = key_piece
+        .strip_prefix("0x")
+        .or_else(|| key_piece.strip_prefix("0X"))
+        .ok_or_else(|| KeyPieceError::MissingPrefix(key_piece.to_string()))?;
+    if digits.is_empty() || digits.len() > 32 {
+        return Err(KeyPieceError::InvalidLength(key_piece.to_string()));
+    }
+    // `from_str_radix` also accepts a leading `+`, so check digits first.
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(KeyPieceError::InvalidDigit(key_piece.to_string()));
+    }
+    u128::from_str_radix(digits, 16)
+        .map_err(|_| KeyPieceError::InvalidDigit(key_piece.to_string()))
+}
+
+/// Parses the `aggregation_keys` of an ARA source registration.
+pub fn parse_aggregation_keys(
+    aggregation_keys: &HashMap<String, String>,
+) -> Result<HashMap<String, u128>, KeyPieceError> {
+    aggregation_keys
+        .iter()
+        .map(|(key_id, key_piece)| {
+            Ok((key_id.clone(), parse_key_piece(key_piece)?))
+        })
+        .collect()
+}
+
+/// (De)serializes key pieces as hex strings.
+mod hex_key_pieces {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        key_pieces: &HashMap<String, u128>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            key_pieces.iter().map(|(key_id, key_piece)| {
+                (key_id, format!("{:#x}", key_piece))
+            }),
+        )
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<String, u128>, D::Error> {
+        let aggregation_keys =
+            HashMap::<String, String>::deserialize(deserializer)?;
+        parse_aggregation_keys(&aggregation_keys)
+            .map_err(serde::de::Error::custom)
+    }
 }
 
 impl Event for AraEvent {
@@ -19,4 +169,107 @@ impl Event for AraEvent {
     fn get_epoch_id(&self) -> Self::EpochId {
         self.epoch_number
     }
+
+    fn get_timestamp(&self) -> u64 {
+        self.source_time
+    }
+
+    fn is_expired(&self, current_time: u64) -> bool {
+        match self.expiry {
+            Some(expiry) => {
+                current_time >= self.source_time.saturating_add(expiry)
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ara_event_epoch() {
+        let clock = EpochClock::new(7 * 24 * 3600).unwrap();
+        let event = AraEvent::new(
+            1,
+            HashMap::from([("campaignCounts".to_string(), 0x159)]),
+            SourceType::Event,
+            1_000_000,
+            &clock,
+        );
+        assert_eq!(event.get_epoch_id(), 1);
+        assert!(clock.check_event(&event).is_ok());
+    }
+
+    #[test]
+    fn test_ara_event_expiry() {
+        let clock = EpochClock::new(7 * 24 * 3600).unwrap();
+        let mut event = AraEvent {
+            expiry: Some(3_600),
+            ..AraEvent::new(
+                1,
+                HashMap::new(),
+                SourceType::Navigation,
+                1_000,
+                &clock,
+            )
+        };
+        assert!(!event.is_expired(1_000));
+        assert!(!event.is_expired(4_599));
+        assert!(event.is_expired(4_600));
+
+        event.expiry = None;
+        assert!(!event.is_expired(u64::MAX));
+    }
+
+    #[test]
+    fn test_parse_key_piece() {
+        assert_eq!(parse_key_piece("0x159"), Ok(0x159));
+        assert_eq!(parse_key_piece("0XaBc"), Ok(0xabc));
+        assert_eq!(
+            parse_key_piece("0xffffffffffffffffffffffffffffffff"),
+            Ok(u128::MAX)
+        );
+
+        let invalid = [
+            ("159", KeyPieceError::MissingPrefix("159".to_string())),
+            ("0x", KeyPieceError::InvalidLength("0x".to_string())),
+            (
+                "0x1ffffffffffffffffffffffffffffffff",
+                KeyPieceError::InvalidLength(
+                    "0x1ffffffffffffffffffffffffffffffff".to_string(),
+                ),
+            ),
+            ("0x+1", KeyPieceError::InvalidDigit("0x+1".to_string())),
+            ("0x15g", KeyPieceError::InvalidDigit("0x15g".to_string())),
+        ];
+        for (key_piece, error) in invalid {
+            assert_eq!(parse_key_piece(key_piece), Err(error));
+        }
+    }
+
+    #[test]
+    fn test_aggregation_keys_serde() {
+        let aggregation_keys = HashMap::from([
+            ("campaignCounts".to_string(), "0x159".to_string()),
+            ("geoValue".to_string(), "0x5".to_string()),
+        ]);
+        let event = AraEvent::new(
+            1,
+            parse_aggregation_keys(&aggregation_keys).unwrap(),
+            SourceType::Navigation,
+            0,
+            &EpochClock::new(7 * 24 * 3600).unwrap(),
+        );
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["aggregatable_sources"]["campaignCounts"], "0x159");
+        let event: AraEvent = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(event.aggregatable_sources["geoValue"], 0x5);
+
+        let mut json = json;
+        json["aggregatable_sources"]["geoValue"] = "5".into();
+        assert!(serde_json::from_value::<AraEvent>(json).is_err());
+    }
 }
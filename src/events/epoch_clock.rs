@@ -0,0 +1,102 @@
+use anyhow::bail;
+
+use crate::events::traits::Event;
+
+/// Derives epochs from event timestamps, with epochs of a fixed length (e.g.
+/// one week) starting at the Unix epoch. Epoch `n` covers the timestamps in
+/// `[n * epoch_length, (n + 1) * epoch_length)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EpochClock {
+    epoch_length: u64,
+}
+
+impl EpochClock {
+    /// Creates a clock with epochs of `epoch_length` seconds.
+    pub fn new(epoch_length: u64) -> Result<Self, anyhow::Error> {
+        if epoch_length == 0 {
+            bail!("Epochs must last at least one second");
+        }
+        Ok(Self { epoch_length })
+    }
+
+    /// Epoch length, in seconds.
+    pub fn get_epoch_length(&self) -> u64 {
+        self.epoch_length
+    }
+
+    /// Returns the epoch that contains `timestamp`.
+    pub fn get_epoch_id(&self, timestamp: u64) -> usize {
+        (timestamp / self.epoch_length) as usize
+    }
+
+    /// Returns the first timestamp of `epoch_id`.
+    pub fn get_epoch_start(&self, epoch_id: usize) -> u64 {
+        epoch_id as u64 * self.epoch_length
+    }
+
+    /// Checks that `event` is in the epoch that contains its timestamp, e.g.
+    /// for events that were deserialized or built field by field.
+    pub fn check_event<E: Event>(&self, event: &E) -> Result<(), anyhow::Error>
+    where
+        E::EpochId: From<usize>,
+    {
+        let epoch_id = self.get_epoch_id(event.get_timestamp());
+        if event.get_epoch_id() != E::EpochId::from(epoch_id) {
+            bail!(
+                "Event in epoch {:?} has timestamp {} from epoch {}",
+                event.get_epoch_id(),
+                event.get_timestamp(),
+                epoch_id
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns the epochs that overlap with the time range `[start, end]`,
+    /// most recent first like `EpochReportRequest::get_epoch_ids`.
+    pub fn get_epoch_ids(&self, start: u64, end: u64) -> Vec<usize> {
+        if start > end {
+            return vec![];
+        }
+        (self.get_epoch_id(start)..=self.get_epoch_id(end))
+            .rev()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::simple_event::SimpleEvent;
+
+    #[test]
+    fn test_epoch_clock() {
+        assert!(EpochClock::new(0).is_err());
+
+        let week = 7 * 24 * 3600;
+        let clock = EpochClock::new(week).unwrap();
+        assert_eq!(clock.get_epoch_id(0), 0);
+        assert_eq!(clock.get_epoch_id(week - 1), 0);
+        assert_eq!(clock.get_epoch_id(week), 1);
+        assert_eq!(clock.get_epoch_start(3), 3 * week);
+
+        assert_eq!(clock.get_epoch_ids(week - 1, 3 * week), vec![3, 2, 1, 0]);
+        assert_eq!(clock.get_epoch_ids(week, week + 1), vec![1]);
+        assert!(clock.get_epoch_ids(2, 1).is_empty());
+    }
+
+    #[test]
+    fn test_check_event() {
+        let clock = EpochClock::new(100).unwrap();
+        let mut event = SimpleEvent {
+            id: 1,
+            epoch_number: 1,
+            timestamp: 150,
+            event_key: 3,
+        };
+        assert!(clock.check_event(&event).is_ok());
+
+        event.epoch_number = 2;
+        assert!(clock.check_event(&event).is_err());
+    }
+}
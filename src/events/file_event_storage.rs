@@ -0,0 +1,349 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::events::{
+    hashmap_event_storage::VecEpochEvents,
+    traits::{Event, EventStorage, RelevantEventSelector},
+};
+
+/// Event storage backed by an append-only log on disk, so that registered
+/// events survive restarts.
+///
+/// Each event is appended to the log as a line of JSON and synced to disk
+/// before `add_event` returns. An in-memory index maps each epoch to the
+/// offsets of its events in the log, sorted by timestamp, so retrieving an
+/// epoch only reads the events of that epoch. The index is rebuilt when the log
+/// is opened, and a partial line left by a crash in the middle of an append is
+/// discarded.
+///
+/// Deleting events compacts the log: the remaining events are written to a
+/// temporary file which atomically replaces the log, so a crash leaves either
+/// the old or the new log.
+pub struct FileEventStorage<E: Event, RES: RelevantEventSelector<Event = E>> {
+    path: PathBuf,
+    file: File,
+
+    /// Length of the log, i.e. offset of the next event.
+    len: u64,
+
+    /// (timestamp, offset) of the events of each epoch, sorted by timestamp
+    /// and then by offset.
+    index: HashMap<E::EpochId, Vec<(u64, u64)>>,
+
+    _phantom: PhantomData<RES>,
+}
+
+impl<E, RES> FileEventStorage<E, RES>
+where
+    E: Event + Serialize + DeserializeOwned,
+    RES: RelevantEventSelector<Event = E>,
+{
+    /// Opens the event log at `path`, or creates an empty one if the file
+    /// does not exist yet.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref().to_path_buf();
+
+        let tmp_path = Self::tmp_path(&path);
+        if tmp_path.exists() {
+            fs::remove_file(&tmp_path)
+                .context("Failed to remove interrupted compaction")?;
+        }
+
+        let file = Self::open_log(&path)?;
+
+        let mut index = HashMap::new();
+        let mut len = 0;
+        let mut reader = BufReader::new(&file);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let num_bytes = reader.read_line(&mut line)?;
+            if num_bytes == 0 {
+                break;
+            }
+            if !line.ends_with('\n') {
+                // Interrupted append, the event was never acknowledged.
+                file.set_len(len)?;
+                break;
+            }
+            let event: E = serde_json::from_str(&line)
+                .context("Failed to parse event log")?;
+            Self::index_event(&mut index, &event, len);
+            len += num_bytes as u64;
+        }
+
+        Ok(Self {
+            path,
+            file,
+            len,
+            index,
+            _phantom: PhantomData,
+        })
+    }
+
+    fn open_log(path: &Path) -> Result<File, anyhow::Error> {
+        OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .context("Failed to open event log")
+    }
+
+    /// Adds an event at `offset` to `index`, after the events with a smaller
+    /// or equal timestamp.
+    fn index_event(
+        index: &mut HashMap<E::EpochId, Vec<(u64, u64)>>,
+        event: &E,
+        offset: u64,
+    ) {
+        let timestamp = event.get_timestamp();
+        let epoch = index.entry(event.get_epoch_id()).or_default();
+        let position = epoch.partition_point(|(t, _)| *t <= timestamp);
+        epoch.insert(position, (timestamp, offset));
+    }
+
+    fn tmp_path(path: &Path) -> PathBuf {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        PathBuf::from(tmp_path)
+    }
+
+    /// Rewrites the log with only the events for which `keep` returns true.
+    fn compact(
+        &mut self,
+        keep: impl Fn(&E) -> bool,
+    ) -> Result<(), anyhow::Error> {
+        let mut offsets: Vec<u64> = self
+            .index
+            .values()
+            .flatten()
+            .map(|(_, offset)| *offset)
+            .collect();
+        offsets.sort_unstable();
+
+        let tmp_path = Self::tmp_path(&self.path);
+        let tmp_file = File::create(&tmp_path)
+            .context("Failed to create temporary event log")?;
+        let mut writer = BufWriter::new(tmp_file);
+        let mut index = HashMap::new();
+        let mut len = 0;
+        for offset in offsets {
+            let event = self.read_event(offset)?;
+            if !keep(&event) {
+                continue;
+            }
+            let mut line = serde_json::to_string(&event)?;
+            line.push('\n');
+            writer.write_all(line.as_bytes())?;
+            Self::index_event(&mut index, &event, len);
+            len += line.len() as u64;
+        }
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+
+        fs::rename(&tmp_path, &self.path)
+            .context("Failed to replace event log")?;
+        if let Some(dir) = self.path.parent() {
+            if let Ok(dir) = File::open(dir) {
+                let _ = dir.sync_all();
+            }
+        }
+
+        self.file = Self::open_log(&self.path)?;
+        self.len = len;
+        self.index = index;
+        Ok(())
+    }
+
+    fn read_event(&self, offset: u64) -> Result<E, anyhow::Error> {
+        let mut reader = BufReader::new(&self.file);
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if !line.ends_with('\n') {
+            bail!("Truncated event at offset {}", offset);
+        }
+        let event =
+            serde_json::from_str(&line).context("Failed to parse event")?;
+        Ok(event)
+    }
+}
+
+impl<E, RES> EventStorage for FileEventStorage<E, RES>
+where
+    E: Event + Serialize + DeserializeOwned,
+    RES: RelevantEventSelector<Event = E>,
+{
+    type Event = E;
+    type EpochEvents = VecEpochEvents<E>;
+    type RelevantEventSelector = RES;
+    type Error = anyhow::Error;
+
+    fn add_event(&mut self, event: E) -> Result<(), Self::Error> {
+        let mut line = serde_json::to_string(&event)?;
+        line.push('\n');
+
+        let result = (&self.file)
+            .write_all(line.as_bytes())
+            .and_then(|_| self.file.sync_data());
+        if let Err(err) = result {
+            // Drop the partial line, so later appends stay readable.
+            let _ = self.file.set_len(self.len);
+            return Err(err).context("Failed to append event");
+        }
+
+        Self::index_event(&mut self.index, &event, self.len);
+        self.len += line.len() as u64;
+        Ok(())
+    }
+
+    fn get_relevant_epoch_events(
+        &self,
+        epoch_id: &E::EpochId,
+        selector: &RES,
+    ) -> Result<Option<VecEpochEvents<E>>, Self::Error> {
+        let Some(offsets) = self.index.get(epoch_id) else {
+            return Ok(None);
+        };
+
+        let mut events = vec![];
+        for (_, offset) in offsets {
+            let event = self.read_event(*offset)?;
+            if selector.is_relevant_event(&event) {
+                events.push(event);
+            }
+        }
+        Ok(Some(events))
+    }
+
+    fn delete_epochs_before(
+        &mut self,
+        epoch_id: &E::EpochId,
+    ) -> Result<(), Self::Error> {
+        if self.index.keys().all(|epoch| epoch >= epoch_id) {
+            return Ok(());
+        }
+        self.compact(|event| event.get_epoch_id() >= *epoch_id)
+    }
+
+    fn delete_events(
+        &mut self,
+        predicate: impl Fn(&E) -> bool,
+    ) -> Result<(), Self::Error> {
+        self.compact(|event| !predicate(event))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        events::{epoch_clock::EpochClock, simple_event::SimpleEvent},
+        queries::simple_last_touch_histogram::SimpleRelevantEventSelector,
+    };
+
+    type Storage = FileEventStorage<SimpleEvent, SimpleRelevantEventSelector>;
+
+    fn event(id: usize, epoch_number: usize, event_key: usize) -> SimpleEvent {
+        let clock = EpochClock::new(100).unwrap();
+        let timestamp = clock.get_epoch_start(epoch_number);
+        SimpleEvent::new(id, timestamp, event_key, &clock)
+    }
+
+    #[test]
+    fn test_file_event_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.log");
+        let all = SimpleRelevantEventSelector { lambda: |_| true };
+
+        let mut storage = Storage::open(&path).unwrap();
+        storage.add_event(event(1, 1, 3)).unwrap();
+        storage.add_event(event(2, 2, 1)).unwrap();
+        storage.add_event(event(3, 1, 5)).unwrap();
+
+        let events = storage.get_relevant_epoch_events(&1, &all).unwrap();
+        let ids: Vec<usize> = events.unwrap().iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![1, 3]);
+        assert!(storage
+            .get_relevant_epoch_events(&3, &all)
+            .unwrap()
+            .is_none());
+
+        let selector = SimpleRelevantEventSelector {
+            lambda: |e| e.event_key > 3,
+        };
+        let events = storage.get_relevant_epoch_events(&1, &selector).unwrap();
+        let ids: Vec<usize> = events.unwrap().iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![3]);
+        drop(storage);
+
+        // Events survive a restart, and new events are appended after them.
+        let mut storage = Storage::open(&path).unwrap();
+        storage.add_event(event(4, 2, 2)).unwrap();
+        let events = storage.get_relevant_epoch_events(&2, &all).unwrap();
+        let ids: Vec<usize> = events.unwrap().iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![2, 4]);
+    }
+
+    #[test]
+    fn test_file_event_storage_interrupted_append() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.log");
+        let all = SimpleRelevantEventSelector { lambda: |_| true };
+
+        let mut storage = Storage::open(&path).unwrap();
+        storage.add_event(event(1, 1, 3)).unwrap();
+        drop(storage);
+
+        // Simulate a crash in the middle of an append.
+        let mut log = fs::read_to_string(&path).unwrap();
+        log.push_str("{\"id\":2,\"epoch_nu");
+        fs::write(&path, log).unwrap();
+
+        let mut storage = Storage::open(&path).unwrap();
+        storage.add_event(event(3, 1, 3)).unwrap();
+        let events = storage.get_relevant_epoch_events(&1, &all).unwrap();
+        let ids: Vec<usize> = events.unwrap().iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_file_event_storage_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.log");
+        let all = SimpleRelevantEventSelector { lambda: |_| true };
+
+        let mut storage = Storage::open(&path).unwrap();
+        for (id, epoch_number) in [(1, 1), (2, 2), (3, 1), (4, 3)] {
+            storage.add_event(event(id, epoch_number, 0)).unwrap();
+        }
+        storage.delete_epochs_before(&2).unwrap();
+        assert!(storage
+            .get_relevant_epoch_events(&1, &all)
+            .unwrap()
+            .is_none());
+
+        // Appends after a compaction go to the new log.
+        storage.add_event(event(5, 2, 0)).unwrap();
+        drop(storage);
+
+        // A leftover from an interrupted compaction is ignored.
+        fs::write(Storage::tmp_path(&path), "{\"id\":1").unwrap();
+        let storage = Storage::open(&path).unwrap();
+        let events = storage.get_relevant_epoch_events(&2, &all).unwrap();
+        let ids: Vec<usize> = events.unwrap().iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![2, 5]);
+        let events = storage.get_relevant_epoch_events(&3, &all).unwrap();
+        assert_eq!(events.unwrap().len(), 1);
+        assert!(!Storage::tmp_path(&path).exists());
+    }
+}
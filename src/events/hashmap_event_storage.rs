@@ -13,7 +13,8 @@ impl<E: Event> EpochEvents for VecEpochEvents<E> {
 }
 
 /// A simple in-memory event storage. Stores a mapping of epoch id to epoch
-/// events, where each epoch events is just a vec of events.
+/// events, where each epoch events is just a vec of events sorted by
+/// timestamp (events with the same timestamp stay in insertion order).
 /// Clones events when asked to retrieve events for an epoch.
 #[derive(Debug, Default)]
 pub struct HashMapEventStorage<E: Event, RES: RelevantEventSelector<Event = E>>
@@ -46,7 +47,9 @@ where
     fn add_event(&mut self, event: E) -> Result<(), Self::Error> {
         let epoch_id = event.get_epoch_id();
         let epoch = self.epochs.entry(epoch_id).or_default();
-        epoch.push(event);
+        let timestamp = event.get_timestamp();
+        let index = epoch.partition_point(|e| e.get_timestamp() <= timestamp);
+        epoch.insert(index, event);
         Ok(())
     }
 
@@ -66,4 +69,131 @@ where
         });
         Ok(events)
     }
+
+    fn delete_events(
+        &mut self,
+        predicate: impl Fn(&E) -> bool,
+    ) -> Result<(), Self::Error> {
+        self.epochs.retain(|_, events| {
+            events.retain(|event| !predicate(event));
+            !events.is_empty()
+        });
+        Ok(())
+    }
+
+    fn delete_all_events(&mut self) -> Result<(), Self::Error> {
+        self.epochs.clear();
+        Ok(())
+    }
+
+    fn delete_epochs_before(
+        &mut self,
+        epoch_id: &E::EpochId,
+    ) -> Result<(), Self::Error> {
+        self.epochs.retain(|epoch, _| epoch >= epoch_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        events::{
+            ara_event::{AraEvent, SourceType},
+            epoch_clock::EpochClock,
+        },
+        queries::ara_histogram::AraRelevantEventSelector,
+    };
+
+    /// Event in `epoch_number`, with epochs of 100 seconds.
+    fn ara_event(
+        id: usize,
+        epoch_number: usize,
+        expiry: Option<u64>,
+    ) -> AraEvent {
+        let clock = EpochClock::new(100).unwrap();
+        AraEvent {
+            expiry,
+            ..AraEvent::new(
+                id,
+                HashMap::new(),
+                SourceType::Navigation,
+                clock.get_epoch_start(epoch_number),
+                &clock,
+            )
+        }
+    }
+
+    #[test]
+    fn test_retention() {
+        let mut storage =
+            HashMapEventStorage::<AraEvent, AraRelevantEventSelector>::new();
+        storage.add_event(ara_event(1, 1, None)).unwrap();
+        storage.add_event(ara_event(2, 2, Some(200))).unwrap();
+        storage.add_event(ara_event(3, 2, None)).unwrap();
+        storage.add_event(ara_event(4, 3, Some(50))).unwrap();
+
+        let ids = |storage: &HashMapEventStorage<_, _>,
+                   epoch|
+         -> Option<Vec<usize>> {
+            let selector = AraRelevantEventSelector::default();
+            storage
+                .get_relevant_epoch_events(&epoch, &selector)
+                .unwrap()
+                .map(|events| events.iter().map(|e: &AraEvent| e.id).collect())
+        };
+
+        // Event 4 expires at 350, while its epoch 3 is still the current
+        // epoch.
+        storage.delete_expired_events(349).unwrap();
+        assert_eq!(ids(&storage, 3), Some(vec![4]));
+        storage.delete_expired_events(350).unwrap();
+        assert_eq!(ids(&storage, 1), Some(vec![1]));
+        assert_eq!(ids(&storage, 2), Some(vec![2, 3]));
+        assert_eq!(ids(&storage, 3), None);
+
+        storage.delete_epochs_before(&2).unwrap();
+        assert_eq!(ids(&storage, 1), None);
+        storage.delete_expired_events(400).unwrap();
+        assert_eq!(ids(&storage, 2), Some(vec![3]));
+    }
+
+    #[test]
+    fn test_events_sorted_by_timestamp() {
+        let mut storage =
+            HashMapEventStorage::<AraEvent, AraRelevantEventSelector>::new();
+        for (id, source_time) in [(1, 30), (2, 10), (3, 30), (4, 20)] {
+            let mut event = ara_event(id, 1, None);
+            event.source_time = source_time;
+            storage.add_event(event).unwrap();
+        }
+
+        let selector = AraRelevantEventSelector::default();
+        let events = storage.get_relevant_epoch_events(&1, &selector).unwrap();
+        let ids: Vec<usize> = events.unwrap().iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![2, 4, 1, 3]);
+    }
+
+    #[test]
+    fn test_delete_events() {
+        let mut storage =
+            HashMapEventStorage::<AraEvent, AraRelevantEventSelector>::new();
+        for id in 1..=4 {
+            storage.add_event(ara_event(id, id % 2, None)).unwrap();
+        }
+        let selector = AraRelevantEventSelector::default();
+
+        storage.delete_events(|event| event.id == 2).unwrap();
+        let events = storage.get_relevant_epoch_events(&0, &selector).unwrap();
+        assert_eq!(events.unwrap().len(), 1);
+
+        storage.delete_all_events().unwrap();
+        for epoch in 0..=1 {
+            let events = storage
+                .get_relevant_epoch_events(&epoch, &selector)
+                .unwrap();
+            assert!(events.is_none());
+        }
+    }
 }
@@ -1,4 +1,6 @@
 pub mod ara_event;
+pub mod epoch_clock;
+pub mod file_event_storage;
 pub mod hashmap_event_storage;
 pub mod simple_event;
 pub mod traits;
@@ -1,20 +1,47 @@
-use crate::events::traits::Event;
+use serde::{Deserialize, Serialize};
+
+use crate::events::{epoch_clock::EpochClock, traits::Event};
 
 /// A barebones event type for testing and demo purposes. See ara_event for a
 /// richer type.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SimpleEvent {
     pub id: usize,
+
+    /// Epoch that contains `timestamp`, e.g. derived with `new`.
     pub epoch_number: usize,
+
+    pub timestamp: u64,
     pub event_key: usize,
 }
 
+impl SimpleEvent {
+    /// Creates an event at `timestamp`, in the epoch given by `epoch_clock`.
+    pub fn new(
+        id: usize,
+        timestamp: u64,
+        event_key: usize,
+        epoch_clock: &EpochClock,
+    ) -> Self {
+        Self {
+            id,
+            epoch_number: epoch_clock.get_epoch_id(timestamp),
+            timestamp,
+            event_key,
+        }
+    }
+}
+
 impl Event for SimpleEvent {
     type EpochId = usize;
 
     fn get_epoch_id(&self) -> Self::EpochId {
         self.epoch_number
     }
+
+    fn get_timestamp(&self) -> u64 {
+        self.timestamp
+    }
 }
 
 #[cfg(test)]
@@ -23,11 +50,10 @@ mod tests {
 
     #[test]
     fn test_simple_event() {
-        let event = SimpleEvent {
-            id: 1,
-            epoch_number: 1,
-            event_key: 3,
-        };
-        assert_eq!(event.id, 1);
+        let clock = EpochClock::new(100).unwrap();
+        let event = SimpleEvent::new(2, 250, 3, &clock);
+        assert_eq!(event.id, 2);
+        assert_eq!(event.get_epoch_id(), 2);
+        assert!(clock.check_event(&event).is_ok());
     }
 }
@@ -1,7 +1,8 @@
 use std::{fmt::Debug, hash::Hash};
 
-/// Marker trait with bounds for epoch identifiers.
-pub trait EpochId: Hash + std::cmp::Eq + Clone + Debug {}
+/// Marker trait with bounds for epoch identifiers. Epochs are ordered, so
+/// that storages can drop events older than a given epoch.
+pub trait EpochId: Hash + std::cmp::Eq + Ord + Clone + Debug {}
 
 /// Default EpochId
 impl EpochId for usize {}
@@ -12,6 +13,18 @@ pub trait Event: Debug {
     // TODO(https://github.com/columbia/pdslib/issues/18): add source/trigger information for Big Bird / Level 2.
 
     fn get_epoch_id(&self) -> Self::EpochId;
+
+    /// Time at which the event happened, in seconds since the Unix epoch.
+    /// Events within an epoch are attributed in timestamp order, and requests
+    /// can select events with an exact time window.
+    fn get_timestamp(&self) -> u64;
+
+    /// Whether the event can't be attributed anymore at `current_time`, in
+    /// seconds since the Unix epoch. Events never expire by default, event
+    /// types with a TTL can override this.
+    fn is_expired(&self, _current_time: u64) -> bool {
+        false
+    }
 }
 
 /// Collection of events for a given epoch.
@@ -50,4 +63,33 @@ pub trait EventStorage {
         epoch_id: &<Self::Event as Event>::EpochId,
         relevant_event_selector: &Self::RelevantEventSelector,
     ) -> Result<Option<Self::EpochEvents>, Self::Error>;
+
+    /// Deletes all the events for which `predicate` returns true.
+    fn delete_events(
+        &mut self,
+        predicate: impl Fn(&Self::Event) -> bool,
+    ) -> Result<(), Self::Error>;
+
+    /// Deletes all the stored events.
+    fn delete_all_events(&mut self) -> Result<(), Self::Error> {
+        self.delete_events(|_| true)
+    }
+
+    /// Deletes all the events of epochs strictly older than `epoch_id`, e.g.
+    /// epochs that are out of the maximum attribution window.
+    fn delete_epochs_before(
+        &mut self,
+        epoch_id: &<Self::Event as Event>::EpochId,
+    ) -> Result<(), Self::Error> {
+        self.delete_events(|event| event.get_epoch_id() < *epoch_id)
+    }
+
+    /// Deletes the events that are expired at `current_time`, according to
+    /// `Event::is_expired`.
+    fn delete_expired_events(
+        &mut self,
+        current_time: u64,
+    ) -> Result<(), Self::Error> {
+        self.delete_events(|event| event.is_expired(current_time))
+    }
 }
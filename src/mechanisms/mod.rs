@@ -1,10 +1,35 @@
+pub mod randomized_response;
+pub mod samplers;
+
 /// L1 and L2 norms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum NormType {
     L1,
-    L2, // Unused for now
+    L2,
 }
 
-/// Noise scale for the mechanism. Currently only Laplace noise is supported.
+/// Noise scale for the mechanism.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum NoiseScale {
     Laplace(f64), // b parameter for Lap(b)
+
+    /// Epsilon of the randomized response applied on device, for reports
+    /// whose sensitivity is 1 if the device's data can change the report at
+    /// all, and 0 otherwise.
+    RandomizedResponse(f64),
+
+    Gaussian(f64), // Standard deviation sigma for N(0, sigma^2)
+}
+
+impl NoiseScale {
+    /// Norm in which the sensitivity of reports is measured for this noise:
+    /// L2 for Gaussian noise, L1 otherwise.
+    pub fn get_norm_type(&self) -> NormType {
+        match self {
+            NoiseScale::Gaussian(_) => NormType::L2,
+            NoiseScale::Laplace(_) | NoiseScale::RandomizedResponse(_) => {
+                NormType::L1
+            }
+        }
+    }
 }
@@ -0,0 +1,71 @@
+use rand::{CryptoRng, Rng};
+
+/// k-ary randomized response over the values `0..num_values`: returns `value`
+/// with probability `e^epsilon / (e^epsilon + num_values - 1)`, and each of
+/// the other values otherwise, uniformly. The output is epsilon-DP with
+/// respect to `value`.
+pub fn randomized_response<R: Rng + CryptoRng + ?Sized>(
+    value: u64,
+    num_values: u64,
+    epsilon: f64,
+    rng: &mut R,
+) -> u64 {
+    if num_values <= 1 {
+        return value;
+    }
+
+    // Same as the probability above, without overflowing for large epsilons.
+    let keep_probability =
+        1.0 / (1.0 + (num_values - 1) as f64 * (-epsilon).exp());
+    if rng.random::<f64>() < keep_probability {
+        return value;
+    }
+
+    // Pick one of the `num_values - 1` other values.
+    let other_value = rng.random_range(0..num_values - 1);
+    if other_value >= value {
+        other_value + 1
+    } else {
+        other_value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn test_randomized_response_frequencies() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let num_samples = 100_000;
+        let epsilon = 2.0_f64.ln();
+
+        // With 3 values and epsilon = ln(2), the true value is kept with
+        // probability 1/2 and each other value is returned w.p. 1/4.
+        let mut counts = [0; 3];
+        for _ in 0..num_samples {
+            counts[randomized_response(1, 3, epsilon, &mut rng) as usize] += 1;
+        }
+        let expected = [0.25, 0.5, 0.25];
+        for (count, expected) in counts.iter().zip(expected) {
+            let frequency = *count as f64 / num_samples as f64;
+            assert!((frequency - expected).abs() < 0.01);
+        }
+
+        // Epsilon = 0 is uniform, and a huge epsilon always keeps the value.
+        let mut counts = [0; 4];
+        for _ in 0..num_samples {
+            counts[randomized_response(3, 4, 0.0, &mut rng) as usize] += 1;
+        }
+        for count in counts {
+            let frequency = count as f64 / num_samples as f64;
+            assert!((frequency - 0.25).abs() < 0.01);
+        }
+        for _ in 0..1000 {
+            assert_eq!(randomized_response(2, 8, f64::INFINITY, &mut rng), 2);
+        }
+        assert_eq!(randomized_response(0, 1, 0.0, &mut rng), 0);
+    }
+}
@@ -0,0 +1,146 @@
+//! Noise samplers for on-device noise and for simulating the aggregator.
+//!
+//! Samplers only take cryptographically secure RNGs, e.g. `rand::rng()` or
+//! `StdRng`. They use floating point arithmetic, so they are not hardened
+//! against floating point attacks on the low-order bits.
+
+use rand::{CryptoRng, Rng};
+
+/// Returns a sample from Exp(1).
+fn sample_exponential<R: Rng + CryptoRng + ?Sized>(rng: &mut R) -> f64 {
+    // `random` is in [0, 1), so the logarithm is finite.
+    -(1.0 - rng.random::<f64>()).ln()
+}
+
+/// Returns a sample from the Laplace distribution Lap(b), with variance
+/// `2 * scale^2`. A non-positive scale gives no noise.
+pub fn sample_laplace<R: Rng + CryptoRng + ?Sized>(
+    scale: f64,
+    rng: &mut R,
+) -> f64 {
+    if scale <= 0.0 {
+        return 0.0;
+    }
+    // The difference of two exponentials is a Laplace.
+    scale * (sample_exponential(rng) - sample_exponential(rng))
+}
+
+/// Returns a sample from the Gaussian distribution N(0, sigma^2). A
+/// non-positive sigma gives no noise.
+pub fn sample_gaussian<R: Rng + CryptoRng + ?Sized>(
+    sigma: f64,
+    rng: &mut R,
+) -> f64 {
+    if sigma <= 0.0 {
+        return 0.0;
+    }
+    // Box-Muller transform.
+    let radius = (2.0 * sample_exponential(rng)).sqrt();
+    let angle = 2.0 * std::f64::consts::PI * rng.random::<f64>();
+    sigma * radius * angle.cos()
+}
+
+/// Returns a sample from the discrete Laplace distribution over the
+/// integers, with probability proportional to `exp(-|x| / scale)`. Its
+/// variance is `2q / (1 - q)^2` with `q = exp(-1 / scale)`. A non-positive
+/// scale gives no noise.
+pub fn sample_discrete_laplace<R: Rng + CryptoRng + ?Sized>(
+    scale: f64,
+    rng: &mut R,
+) -> i64 {
+    if scale <= 0.0 {
+        return 0;
+    }
+    // The difference of two geometric variables with parameter
+    // 1 - exp(-1 / scale), each obtained by rounding down an exponential.
+    let geometric = |rng: &mut R| (scale * sample_exponential(rng)).floor();
+    (geometric(rng) - geometric(rng)) as i64
+}
+
+/// Returns a sample from the discrete Gaussian distribution over the
+/// integers, with probability proportional to `exp(-x^2 / (2 sigma^2))`, by
+/// rejection sampling from a discrete Laplace
+/// (https://arxiv.org/abs/2004.00010, Alg. 3). Its variance is at most
+/// `sigma^2`, and close to it for `sigma` above 1. A non-positive sigma gives
+/// no noise.
+pub fn sample_discrete_gaussian<R: Rng + CryptoRng + ?Sized>(
+    sigma: f64,
+    rng: &mut R,
+) -> i64 {
+    if sigma <= 0.0 {
+        return 0;
+    }
+    let scale = sigma.floor() + 1.0;
+    let sigma_squared = sigma * sigma;
+    loop {
+        let candidate = sample_discrete_laplace(scale, rng);
+        let distance = (candidate.abs() as f64) - sigma_squared / scale;
+        let acceptance_probability =
+            (-distance * distance / (2.0 * sigma_squared)).exp();
+        if rng.random::<f64>() < acceptance_probability {
+            return candidate;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    const NUM_SAMPLES: usize = 100_000;
+
+    /// Checks the empirical mean and variance of `samples`, with a relative
+    /// tolerance on the variance.
+    fn check_moments(samples: &[f64], expected_variance: f64) {
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let variance =
+            samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        assert!(mean.abs() < 0.05 * expected_variance.sqrt());
+        assert!(
+            (variance - expected_variance).abs() < 0.05 * expected_variance,
+            "variance {variance}, expected {expected_variance}"
+        );
+    }
+
+    #[test]
+    fn test_continuous_variances() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for scale in [0.5, 1.0, 10.0] {
+            let samples: Vec<f64> = (0..NUM_SAMPLES)
+                .map(|_| sample_laplace(scale, &mut rng))
+                .collect();
+            check_moments(&samples, 2.0 * scale * scale);
+
+            let samples: Vec<f64> = (0..NUM_SAMPLES)
+                .map(|_| sample_gaussian(scale, &mut rng))
+                .collect();
+            check_moments(&samples, scale * scale);
+        }
+        assert_eq!(sample_laplace(0.0, &mut rng), 0.0);
+        assert_eq!(sample_gaussian(-1.0, &mut rng), 0.0);
+    }
+
+    #[test]
+    fn test_discrete_variances() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for scale in [0.5, 1.0, 10.0] {
+            let samples: Vec<f64> = (0..NUM_SAMPLES)
+                .map(|_| sample_discrete_laplace(scale, &mut rng) as f64)
+                .collect();
+            let q = (-1.0 / scale).exp();
+            check_moments(&samples, 2.0 * q / (1.0 - q).powi(2));
+        }
+
+        for sigma in [2.0, 5.0, 20.0] {
+            let samples: Vec<f64> = (0..NUM_SAMPLES)
+                .map(|_| sample_discrete_gaussian(sigma, &mut rng) as f64)
+                .collect();
+            check_moments(&samples, sigma * sigma);
+        }
+        assert_eq!(sample_discrete_laplace(0.0, &mut rng), 0);
+        assert_eq!(sample_discrete_gaussian(0.0, &mut rng), 0);
+    }
+}
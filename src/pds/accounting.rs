@@ -0,0 +1,412 @@
+use crate::{
+    budget::{
+        approx_dp_filter::ApproxDpBudget, pure_dp_filter::PureDPBudget,
+        rdp_filter::RdpBudget, traits::Budget, zcdp_filter::ZcdpBudget,
+    },
+    mechanisms::NoiseScale,
+};
+
+/// Trait for budgets that the PDS can charge for a report. Each budget type
+/// translates the individual sensitivity of a report and the noise added to
+/// the report into its own notion of privacy loss, which lets the same PDS run
+/// with pure DP, RDP, zCDP or approximate DP filters. The accountant is the
+/// budget type of the filter storage, so requests don't need to know which
+/// filters will charge them.
+pub trait PrivacyLossAccountant: Budget {
+    /// Returns the individual privacy loss of a report with sensitivity
+    /// `individual_sensitivity` when the aggregator adds noise with
+    /// `noise_scale`. A zero sensitivity must give a zero loss.
+    ///
+    /// `capacity` is the capacity of the filters that will be charged, for
+    /// budgets that need losses expressed in the same terms (e.g. RDP orders).
+    fn compute_privacy_loss(
+        individual_sensitivity: f64,
+        noise_scale: &NoiseScale,
+        capacity: &Self,
+    ) -> Self;
+
+    /// Expresses `budget`, e.g. a passive privacy loss, in the same terms as
+    /// `capacity`, so that it can be charged to a filter with that capacity.
+    /// Returns `budget` as is by default.
+    fn express_for_capacity(budget: &Self, _capacity: &Self) -> Self {
+        budget.clone()
+    }
+}
+
+/// Pure DP epsilon of the Laplace mechanism, or `None` if the noise is so
+/// small that the report is not private.
+///
+/// Near-zero noise scales are treated as non-private, i.e. requesting infinite
+/// budget. Pure DP and zCDP filters only accept such requests if they are also
+/// set to infinite capacity, e.g. for debugging, and RDP and approximate DP
+/// filters always reject them. The machine precision `f64::EPSILON` is not
+/// related to privacy.
+fn laplace_epsilon(
+    individual_sensitivity: f64,
+    laplace_scale: f64,
+) -> Option<f64> {
+    if laplace_scale.abs() < f64::EPSILON {
+        return None;
+    }
+    Some(individual_sensitivity / laplace_scale)
+}
+
+/// Pure DP epsilon of the mechanism with `noise_scale`, or `None` if the report
+/// is not pure DP. Randomized response is epsilon-DP for reports that the
+/// device's data can change, i.e. with sensitivity 1. Gaussian noise never
+/// gives pure DP.
+fn pure_dp_epsilon(
+    individual_sensitivity: f64,
+    noise_scale: &NoiseScale,
+) -> Option<f64> {
+    match noise_scale {
+        NoiseScale::Laplace(laplace_scale) => {
+            laplace_epsilon(individual_sensitivity, *laplace_scale)
+        }
+        NoiseScale::RandomizedResponse(epsilon) => {
+            Some(individual_sensitivity * epsilon)
+        }
+        NoiseScale::Gaussian(_) => None,
+    }
+}
+
+/// zCDP rho of the Gaussian mechanism, given the L2 sensitivity of the report
+/// (https://arxiv.org/abs/1605.02065, Prop. 1.6), or `None` if the noise is
+/// so small that the report is not private.
+fn gaussian_rho(individual_sensitivity: f64, sigma: f64) -> Option<f64> {
+    if sigma.abs() < f64::EPSILON {
+        return None;
+    }
+    Some(individual_sensitivity.powi(2) / (2.0 * sigma.powi(2)))
+}
+
+impl PrivacyLossAccountant for PureDPBudget {
+    fn compute_privacy_loss(
+        individual_sensitivity: f64,
+        noise_scale: &NoiseScale,
+        _capacity: &Self,
+    ) -> Self {
+        if individual_sensitivity == 0.0 {
+            return PureDPBudget::Epsilon(0.0);
+        }
+        match pure_dp_epsilon(individual_sensitivity, noise_scale) {
+            Some(epsilon) => PureDPBudget::Epsilon(epsilon),
+            None => PureDPBudget::Infinite,
+        }
+    }
+}
+
+impl PrivacyLossAccountant for ZcdpBudget {
+    /// For Gaussian noise, uses the exact zCDP of the Gaussian mechanism.
+    /// Otherwise, uses the fact that epsilon-DP implies (epsilon^2 / 2)-zCDP
+    /// (https://arxiv.org/abs/1605.02065, Prop. 1.4).
+    fn compute_privacy_loss(
+        individual_sensitivity: f64,
+        noise_scale: &NoiseScale,
+        _capacity: &Self,
+    ) -> Self {
+        if individual_sensitivity == 0.0 {
+            return ZcdpBudget::Rho(0.0);
+        }
+        let rho = match noise_scale {
+            NoiseScale::Gaussian(sigma) => {
+                gaussian_rho(individual_sensitivity, *sigma)
+            }
+            _ => pure_dp_epsilon(individual_sensitivity, noise_scale)
+                .map(|epsilon| epsilon * epsilon / 2.0),
+        };
+        match rho {
+            Some(rho) => ZcdpBudget::Rho(rho),
+            None => ZcdpBudget::Infinite,
+        }
+    }
+}
+
+impl PrivacyLossAccountant for RdpBudget {
+    /// Returns a loss for each order of `capacity`. For Laplace noise, uses the
+    /// closed form from https://arxiv.org/abs/1702.07476, Prop. 6, capped by
+    /// the pure DP epsilon. For randomized response, uses the zCDP bound of
+    /// pure DP, also capped by the pure DP epsilon. For Gaussian noise, the
+    /// RDP of order `alpha` is `alpha * rho` (same paper, Prop. 7).
+    fn compute_privacy_loss(
+        individual_sensitivity: f64,
+        noise_scale: &NoiseScale,
+        capacity: &Self,
+    ) -> Self {
+        let epsilon = pure_dp_epsilon(individual_sensitivity, noise_scale);
+        let rdp_epsilon = |alpha: f64| {
+            let rdp_epsilon = match noise_scale {
+                NoiseScale::Laplace(_) => {
+                    epsilon.map(|epsilon| laplace_rdp(alpha, epsilon))
+                }
+                NoiseScale::RandomizedResponse(_) => epsilon.map(|epsilon| {
+                    (alpha * epsilon * epsilon / 2.0).min(epsilon)
+                }),
+                NoiseScale::Gaussian(sigma) => {
+                    gaussian_rho(individual_sensitivity, *sigma)
+                        .map(|rho| alpha * rho)
+                }
+            };
+            rdp_epsilon.unwrap_or(f64::INFINITY)
+        };
+
+        let alpha_epsilons = capacity
+            .alpha_epsilons
+            .iter()
+            .map(|(alpha, _)| {
+                if individual_sensitivity == 0.0 {
+                    (*alpha, 0.0)
+                } else {
+                    (*alpha, rdp_epsilon(*alpha))
+                }
+            })
+            .collect();
+        RdpBudget { alpha_epsilons }
+    }
+
+    /// Evaluates `budget` on the orders of `capacity`. RDP is non-decreasing
+    /// in the order, so an order that `budget` doesn't have is charged the
+    /// epsilon of the next larger order of `budget`. If there is none, the
+    /// epsilon is infinite and filters reject it.
+    fn express_for_capacity(budget: &Self, capacity: &Self) -> Self {
+        let alpha_epsilons = capacity
+            .alpha_epsilons
+            .iter()
+            .map(|(alpha, _)| {
+                let epsilon = budget
+                    .alpha_epsilons
+                    .iter()
+                    .filter(|(budget_alpha, _)| budget_alpha >= alpha)
+                    .min_by(|(a, _), (b, _)| a.total_cmp(b))
+                    .map_or(f64::INFINITY, |(_, epsilon)| *epsilon);
+                (*alpha, epsilon)
+            })
+            .collect();
+        RdpBudget { alpha_epsilons }
+    }
+}
+
+/// RDP of order `alpha` for the Laplace mechanism with `epsilon =
+/// sensitivity / scale`. The log-sum-exp is rearranged so that large orders
+/// don't overflow.
+fn laplace_rdp(alpha: f64, epsilon: f64) -> f64 {
+    let a = alpha / (2.0 * alpha - 1.0);
+    let b = (alpha - 1.0) / (2.0 * alpha - 1.0);
+    let log_sum = (alpha - 1.0) * epsilon
+        + (a + b * (-(2.0 * alpha - 1.0) * epsilon).exp()).ln();
+    (log_sum / (alpha - 1.0)).min(epsilon)
+}
+
+impl PrivacyLossAccountant for ApproxDpBudget {
+    /// Laplace noise and randomized response give pure DP, so delta is
+    /// always zero. Gaussian noise needs a delta that requests don't specify,
+    /// so it is only supported by zCDP and RDP filters and gets an infinite
+    /// epsilon here, which approximate DP filters reject.
+    fn compute_privacy_loss(
+        individual_sensitivity: f64,
+        noise_scale: &NoiseScale,
+        _capacity: &Self,
+    ) -> Self {
+        let epsilon = if individual_sensitivity == 0.0 {
+            0.0
+        } else {
+            pure_dp_epsilon(individual_sensitivity, noise_scale)
+                .unwrap_or(f64::INFINITY)
+        };
+        ApproxDpBudget {
+            epsilon,
+            delta: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_laplace_losses() {
+        let noise_scale = NoiseScale::Laplace(2.0);
+        assert_eq!(
+            PureDPBudget::compute_privacy_loss(
+                1.0,
+                &noise_scale,
+                &PureDPBudget::Infinite
+            ),
+            PureDPBudget::Epsilon(0.5)
+        );
+        assert_eq!(
+            ZcdpBudget::compute_privacy_loss(
+                1.0,
+                &noise_scale,
+                &ZcdpBudget::Infinite
+            ),
+            ZcdpBudget::Rho(0.125)
+        );
+        assert_eq!(
+            ApproxDpBudget::compute_privacy_loss(
+                1.0,
+                &noise_scale,
+                &ApproxDpBudget {
+                    epsilon: 1.0,
+                    delta: 0.0
+                }
+            ),
+            ApproxDpBudget {
+                epsilon: 0.5,
+                delta: 0.0
+            }
+        );
+
+        // Noiseless reports are infinitely expensive, unless they don't
+        // depend on the device's data.
+        let no_noise = NoiseScale::Laplace(0.0);
+        assert_eq!(
+            PureDPBudget::compute_privacy_loss(
+                1.0,
+                &no_noise,
+                &PureDPBudget::Infinite
+            ),
+            PureDPBudget::Infinite
+        );
+        assert_eq!(
+            PureDPBudget::compute_privacy_loss(
+                0.0,
+                &no_noise,
+                &PureDPBudget::Infinite
+            ),
+            PureDPBudget::Epsilon(0.0)
+        );
+    }
+
+    #[test]
+    fn test_laplace_rdp() {
+        let capacity = RdpBudget {
+            alpha_epsilons: vec![
+                (1.5, 1.0),
+                (2.0, 1.0),
+                (8.0, 1.0),
+                (1e6, 1.0),
+            ],
+        };
+        let loss = RdpBudget::compute_privacy_loss(
+            1.0,
+            &NoiseScale::Laplace(2.0),
+            &capacity,
+        );
+
+        // RDP is increasing in alpha, and bounded by the pure DP epsilon.
+        let epsilons: Vec<f64> =
+            loss.alpha_epsilons.iter().map(|(_, e)| *e).collect();
+        assert!(epsilons.windows(2).all(|w| w[0] <= w[1]));
+        assert!(epsilons.iter().all(|e| *e > 0.0 && *e <= 0.5));
+        assert!((epsilons[3] - 0.5).abs() < 1e-5);
+
+        // Order 2 matches the closed form directly.
+        let expected =
+            ((2.0 / 3.0) * 0.5_f64.exp() + (1.0 / 3.0) * (-1.0_f64).exp()).ln();
+        assert!((epsilons[1] - expected).abs() < 1e-12);
+
+        let zero_loss = RdpBudget::compute_privacy_loss(
+            0.0,
+            &NoiseScale::Laplace(0.0),
+            &capacity,
+        );
+        assert!(zero_loss.alpha_epsilons.iter().all(|(_, e)| *e == 0.0));
+    }
+
+    #[test]
+    fn test_express_rdp_for_capacity() {
+        let budget = RdpBudget {
+            alpha_epsilons: vec![(2.0, 0.1), (8.0, 0.4)],
+        };
+        let capacity = RdpBudget {
+            alpha_epsilons: vec![(2.0, 1.0), (4.0, 1.0), (16.0, 1.0)],
+        };
+        assert_eq!(
+            RdpBudget::express_for_capacity(&budget, &capacity),
+            RdpBudget {
+                alpha_epsilons: vec![
+                    (2.0, 0.1),
+                    (4.0, 0.4),
+                    (16.0, f64::INFINITY)
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_randomized_response_losses() {
+        let noise_scale = NoiseScale::RandomizedResponse(0.5);
+        assert_eq!(
+            PureDPBudget::compute_privacy_loss(
+                1.0,
+                &noise_scale,
+                &PureDPBudget::Infinite
+            ),
+            PureDPBudget::Epsilon(0.5)
+        );
+        assert_eq!(
+            ZcdpBudget::compute_privacy_loss(
+                1.0,
+                &noise_scale,
+                &ZcdpBudget::Infinite
+            ),
+            ZcdpBudget::Rho(0.125)
+        );
+        assert_eq!(
+            PureDPBudget::compute_privacy_loss(
+                0.0,
+                &noise_scale,
+                &PureDPBudget::Infinite
+            ),
+            PureDPBudget::Epsilon(0.0)
+        );
+
+        let capacity = RdpBudget {
+            alpha_epsilons: vec![(2.0, 1.0), (8.0, 1.0)],
+        };
+        let loss =
+            RdpBudget::compute_privacy_loss(1.0, &noise_scale, &capacity);
+        assert_eq!(loss.alpha_epsilons, vec![(2.0, 0.25), (8.0, 0.5)]);
+    }
+
+    #[test]
+    fn test_gaussian_losses() {
+        let noise_scale = NoiseScale::Gaussian(2.0);
+        assert_eq!(
+            ZcdpBudget::compute_privacy_loss(
+                1.0,
+                &noise_scale,
+                &ZcdpBudget::Infinite
+            ),
+            ZcdpBudget::Rho(0.125)
+        );
+
+        let capacity = RdpBudget {
+            alpha_epsilons: vec![(2.0, 1.0), (8.0, 1.0)],
+        };
+        let loss =
+            RdpBudget::compute_privacy_loss(1.0, &noise_scale, &capacity);
+        assert_eq!(loss.alpha_epsilons, vec![(2.0, 0.25), (8.0, 1.0)]);
+
+        // Gaussian noise is not pure DP, unless the report doesn't depend on
+        // the device's data.
+        assert_eq!(
+            PureDPBudget::compute_privacy_loss(
+                1.0,
+                &noise_scale,
+                &PureDPBudget::Infinite
+            ),
+            PureDPBudget::Infinite
+        );
+        assert_eq!(
+            PureDPBudget::compute_privacy_loss(
+                0.0,
+                &noise_scale,
+                &PureDPBudget::Infinite
+            ),
+            PureDPBudget::Epsilon(0.0)
+        );
+    }
+}
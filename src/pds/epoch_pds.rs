@@ -1,30 +1,63 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, ops::RangeBounds};
+
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    budget::{
-        pure_dp_filter::PureDPBudget,
-        traits::{FilterStatus, FilterStorage},
-    },
-    events::traits::{
-        EpochEvents, EpochId, Event, EventStorage, RelevantEventSelector,
+    budget::traits::{FilterStatus, FilterStorage},
+    events::{
+        epoch_clock::EpochClock,
+        traits::{
+            EpochEvents, EpochId, Event, EventStorage, RelevantEventSelector,
+        },
     },
-    mechanisms::{NoiseScale, NormType},
+    mechanisms::NoiseScale,
+    pds::{accounting::PrivacyLossAccountant, traits::DeduplicationKeyStorage},
     queries::traits::{
-        EpochReportRequest, PassivePrivacyLossRequest, ReportRequest,
+        EpochReportRequest, PassivePrivacyLossRequest, QuerierId,
+        RelevantEventsPerEpoch, ReportRequest,
     },
 };
 
-/// Epoch-based private data service, using generic filter
-/// storage and event storage interfaces.
-///
-/// TODO(https://github.com/columbia/pdslib/issues/18): handle multiple queriers
-/// instead of assuming that there is a single querier and using filter_id =
-/// epoch_id
+/// Identifier for a privacy filter. For each epoch, the PDS keeps one filter
+/// per querier, so that a querier exhausting its budget does not affect the
+/// budget of other queriers, and one global filter that bounds the total
+/// privacy loss across all queriers.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FilterId<EI: EpochId, QI: QuerierId> {
+    /// Filter for a given querier and epoch.
+    Querier(EI, QI),
+
+    /// Device-wide filter for a given epoch, shared by all queriers.
+    Global(EI),
+}
+
+/// How the PDS computes the individual sensitivity of each epoch when
+/// several epochs have relevant events (Case 3 in Cookie Monster).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MultiEpochSensitivity {
+    /// Charge every epoch with relevant events the global sensitivity of the
+    /// report.
+    #[default]
+    Global,
+
+    /// [Experimental] Charge each epoch the change of the report when only
+    /// the relevant events of that epoch are removed, capped by the global
+    /// sensitivity. Requests that can't compare reports (see
+    /// `EpochReportRequest::get_report_distance`) still pay the global
+    /// sensitivity.
+    LeaveOneEpochOut,
+}
+
+/// Epoch-based private data service, using generic filter storage, event
+/// storage and deduplication key storage interfaces. The PDS is generic over
+/// the budget type of the filters, as long as the budget knows how to account
+/// for the noise of a report (see `PrivacyLossAccountant`).
 pub struct EpochPrivateDataService<
     FS: FilterStorage,
     ES: EventStorage,
+    DS: DeduplicationKeyStorage,
     Q: EpochReportRequest,
-    ERR: From<FS::Error> + From<ES::Error>,
+    ERR: From<FS::Error> + From<ES::Error> + From<DS::Error>,
 > {
     /// Filter storage interface.
     pub filter_storage: FS,
@@ -32,8 +65,25 @@ pub struct EpochPrivateDataService<
     /// Event storage interface.
     pub event_storage: ES,
 
-    /// Default capacity that will be used for all new epochs
-    pub epoch_capacity: FS::Budget,
+    /// Deduplication key storage interface, for the keys of the requests
+    /// that were already processed.
+    pub deduplication_key_storage: DS,
+
+    /// Derives epochs from timestamps. Events can only be registered in the
+    /// epoch that contains their timestamp.
+    pub epoch_clock: EpochClock,
+
+    /// Default capacity that will be used for all new per-querier filters,
+    /// i.e. for each new (querier, epoch) pair.
+    pub querier_capacity: FS::Budget,
+
+    /// Default capacity that will be used for all new global filters, i.e.
+    /// for each new epoch.
+    pub global_capacity: FS::Budget,
+
+    /// Individual sensitivity of epochs for reports that use multiple
+    /// epochs.
+    pub multi_epoch_sensitivity: MultiEpochSensitivity,
 
     /// Type of accepted queries.
     pub _phantom_request: std::marker::PhantomData<Q>,
@@ -42,32 +92,121 @@ pub struct EpochPrivateDataService<
     pub _phantom_error: std::marker::PhantomData<ERR>,
 }
 
+impl<FS, ES, DS, Q, ERR> EpochPrivateDataService<FS, ES, DS, Q, ERR>
+where
+    FS: FilterStorage,
+    ES: EventStorage,
+    DS: DeduplicationKeyStorage,
+    Q: EpochReportRequest,
+    ERR: From<FS::Error> + From<ES::Error> + From<DS::Error>,
+{
+    /// Creates a PDS with the given storages, epochs and filter capacities,
+    /// using the global sensitivity for reports that use multiple epochs.
+    pub fn new(
+        filter_storage: FS,
+        event_storage: ES,
+        deduplication_key_storage: DS,
+        epoch_clock: EpochClock,
+        querier_capacity: FS::Budget,
+        global_capacity: FS::Budget,
+    ) -> Self {
+        Self {
+            filter_storage,
+            event_storage,
+            deduplication_key_storage,
+            epoch_clock,
+            querier_capacity,
+            global_capacity,
+            multi_epoch_sensitivity: MultiEpochSensitivity::default(),
+            _phantom_request: std::marker::PhantomData,
+            _phantom_error: std::marker::PhantomData,
+        }
+    }
+}
+
 /// API for the epoch-based PDS.
 ///
-/// TODO(https://github.com/columbia/pdslib/issues/21): support more than PureDP
 /// TODO(https://github.com/columbia/pdslib/issues/22): simplify trait bounds?
-impl<EI, E, EE, RES, FS, ES, Q, ERR> EpochPrivateDataService<FS, ES, Q, ERR>
+impl<EI, QI, E, EE, RES, FS, ES, DS, Q, ERR>
+    EpochPrivateDataService<FS, ES, DS, Q, ERR>
 where
-    EI: EpochId,
+    EI: EpochId + From<usize>,
+    QI: QuerierId,
     E: Event<EpochId = EI>,
     EE: EpochEvents,
-    FS: FilterStorage<FilterId = EI, Budget = PureDPBudget>,
+    FS: FilterStorage<FilterId = FilterId<EI, QI>>,
+    FS::Budget: PrivacyLossAccountant,
     RES: RelevantEventSelector<Event = E>,
     ES: EventStorage<Event = E, EpochEvents = EE, RelevantEventSelector = RES>,
+    DS: DeduplicationKeyStorage<EpochId = EI, QuerierId = QI>,
     Q: EpochReportRequest<
         EpochId = EI,
         EpochEvents = EE,
+        QuerierId = QI,
         RelevantEventSelector = RES,
     >,
-    ERR: From<FS::Error> + From<ES::Error>,
+    ERR: From<FS::Error>
+        + From<ES::Error>
+        + From<DS::Error>
+        + From<anyhow::Error>,
 {
-    /// Registers a new event.
+    /// Registers a new event. Fails if the event is not in the epoch that
+    /// contains its timestamp.
     pub fn register_event(&mut self, event: E) -> Result<(), ERR> {
         println!("Registering event {:?}", event);
+        self.epoch_clock.check_event(&event)?;
         self.event_storage.add_event(event)?;
         Ok(())
     }
 
+    /// Deletes all the events, e.g. when the user clears their history.
+    ///
+    /// Deleting events never refunds budget: the filters keep the privacy
+    /// loss of the reports that were computed on the deleted events, since
+    /// these reports have already been released and restoring their budget
+    /// would let queriers learn more about the deleted data.
+    pub fn clear_all_events(&mut self) -> Result<(), ERR> {
+        self.event_storage.delete_all_events()?;
+        Ok(())
+    }
+
+    /// Deletes the events for which `predicate` returns true, e.g. events
+    /// from a given site or querier. Leaves the filters untouched, like
+    /// `clear_all_events`.
+    pub fn clear_events(
+        &mut self,
+        predicate: impl Fn(&E) -> bool,
+    ) -> Result<(), ERR> {
+        self.event_storage.delete_events(predicate)?;
+        Ok(())
+    }
+
+    /// Deletes the events of the epochs in `epoch_range`, e.g. to clear the
+    /// last hour of history, and the deduplication keys recorded in these
+    /// epochs. Leaves the filters untouched, like `clear_all_events`.
+    pub fn clear_epochs(
+        &mut self,
+        epoch_range: impl RangeBounds<EI>,
+    ) -> Result<(), ERR> {
+        self.event_storage.delete_events(|event| {
+            epoch_range.contains(&event.get_epoch_id())
+        })?;
+        self.deduplication_key_storage
+            .delete_keys(|epoch_id| epoch_range.contains(epoch_id))?;
+        Ok(())
+    }
+
+    /// Deletes the events and deduplication keys of the epochs strictly
+    /// older than `epoch_id`, e.g. epochs that are out of the maximum
+    /// attribution window. Leaves the filters untouched, like
+    /// `clear_all_events`.
+    pub fn delete_epochs_before(&mut self, epoch_id: &EI) -> Result<(), ERR> {
+        self.event_storage.delete_epochs_before(epoch_id)?;
+        self.deduplication_key_storage
+            .delete_keys(|key_epoch_id| key_epoch_id < epoch_id)?;
+        Ok(())
+    }
+
     /// Computes a report for the given report request.
     /// This function follows `compute_attribution_report` from the Cookie
     /// Monster Algorithm (https://arxiv.org/pdf/2405.16719, Code Listing 1)
@@ -77,9 +216,39 @@ where
     ) -> Result<<Q as ReportRequest>::Report, ERR> {
         println!("Computing report for request {:?}", request);
 
-        // Collect events from event storage. If an epoch has no relevant
-        // events, don't add it to the mapping.
-        let mut relevant_events_per_epoch: HashMap<EI, EE> = HashMap::new();
+        // Reject invalid requests, and requests whose noise doesn't match
+        // their declared privacy parameters. This only depends on the
+        // request, not on the device's data, so the error can be returned as
+        // is.
+        request.validate()?;
+        if let Some(privacy_parameters) =
+            request.get_requested_privacy_parameters()
+        {
+            privacy_parameters.validate(&request.get_noise_scale())?;
+        }
+
+        // A querier can only get one report per deduplication key. Keys are
+        // recorded once a report is computed, whether or not it attributes
+        // anything, so that refusing a duplicate doesn't depend on the
+        // device's data, and refusals don't consume any budget. Keys are
+        // recorded in the most recent requested epoch, and dropped with it.
+        // Requests without epochs never see any event, so their keys don't
+        // need to be recorded.
+        let deduplication_key = request.get_deduplication_key();
+        if let Some(deduplication_key) = deduplication_key {
+            if self
+                .deduplication_key_storage
+                .contains_key(&request.get_querier_id(), deduplication_key)?
+            {
+                return Ok(Default::default());
+            }
+        }
+
+        // Collect events from event storage, in the order of
+        // `get_epoch_ids`. If an epoch has no relevant events, don't add it to
+        // the mapping.
+        let mut relevant_events_per_epoch: RelevantEventsPerEpoch<EI, EE> =
+            RelevantEventsPerEpoch::new();
         let relevant_event_selector = request.get_relevant_event_selector();
         for epoch_id in request.get_epoch_ids() {
             let epoch_relevant_events =
@@ -89,8 +258,10 @@ where
                 )?;
 
             if let Some(epoch_relevant_events) = epoch_relevant_events {
-                relevant_events_per_epoch
-                    .insert(epoch_id, epoch_relevant_events);
+                if !epoch_relevant_events.is_empty() {
+                    relevant_events_per_epoch
+                        .insert(epoch_id, epoch_relevant_events);
+                }
             }
         }
 
@@ -98,6 +269,26 @@ where
         let num_epochs: usize = relevant_events_per_epoch.len();
         let unbiased_report =
             request.compute_report(&relevant_events_per_epoch);
+        let querier_id = request.get_querier_id();
+
+        // With leave-one-epoch-out sensitivity, the loss of an epoch depends
+        // on the other epochs, so OOB epochs are dropped before consuming any
+        // budget, until the remaining epochs can pay for the final report.
+        let leave_one_epoch_out_sensitivities =
+            match self.multi_epoch_sensitivity {
+                MultiEpochSensitivity::LeaveOneEpochOut if num_epochs > 1 => {
+                    match self.drop_epochs_for_leave_one_epoch_out(
+                        &request,
+                        &mut relevant_events_per_epoch,
+                        num_epochs,
+                    ) {
+                        Ok(sensitivities) => sensitivities,
+                        // Return default report if anything goes wrong.
+                        Err(_) => return Ok(Default::default()),
+                    }
+                }
+                _ => HashMap::new(),
+            };
 
         // Browse epochs in the attribution window
         for epoch_id in request.get_epoch_ids() {
@@ -106,23 +297,24 @@ where
                 relevant_events_per_epoch.get(&epoch_id);
 
             // Step 2. Compute individual loss for current epoch.
-            let individual_privacy_loss = self.compute_individual_privacy_loss(
-                &request,
-                epoch_relevant_events,
-                &unbiased_report,
-                num_epochs,
-            );
-
-            // Initialize filter if necessary.
-            // TODO(https://github.com/columbia/pdslib/issues/18): handle multiple queriers.
-            self.initialize_filter_if_necessary(&epoch_id)?;
+            let (querier_loss, global_loss) = self
+                .compute_individual_privacy_loss(
+                    &request,
+                    epoch_relevant_events,
+                    &unbiased_report,
+                    num_epochs,
+                    leave_one_epoch_out_sensitivities.get(&epoch_id).copied(),
+                );
 
             // Step 3. Try to consume budget from current epoch, drop events if
-            // OOB.
-            match self
-                .filter_storage
-                .check_and_consume(&epoch_id, &individual_privacy_loss)
-            {
+            // OOB. Both the querier filter and the global filter need to
+            // accept the loss.
+            match self.check_and_consume_epochs(
+                std::slice::from_ref(&epoch_id),
+                &querier_id,
+                &querier_loss,
+                &global_loss,
+            ) {
                 Ok(FilterStatus::Continue) => {
                     // The budget is not depleted, keep events.
                 }
@@ -137,52 +329,175 @@ where
             }
         }
 
-        // Now that we've dropped OOB epochs, we can compute the final report.
+        // Now that we've dropped OOB epochs, we can compute the final report,
+        // and add on-device noise if the request needs any.
         let filtered_report =
             request.compute_report(&relevant_events_per_epoch);
-        Ok(filtered_report)
+        if let (Some(deduplication_key), Some(epoch_id)) =
+            (deduplication_key, request.get_epoch_ids().into_iter().max())
+        {
+            self.deduplication_key_storage.add_key(
+                epoch_id,
+                querier_id,
+                deduplication_key,
+            )?;
+        }
+        Ok(request.perturb_report(filtered_report))
     }
 
     /// [Experimental] Accounts for passive privacy loss. Can fail if the
     /// implementation has an error, but failure must not leak the state of
     /// the filters.
     ///
-    /// TODO(https://github.com/columbia/pdslib/issues/16): what are the semantics of passive loss queries that go over the filter
-    /// capacity?
+    /// The request is all-or-nothing: if the querier filter or the global
+    /// filter of any requested epoch cannot afford the loss, the request is
+    /// rejected with `OutOfBudget` and no filter is modified. The loss is
+    /// expressed for each filter's capacity, e.g. for its RDP orders.
     pub fn account_for_passive_privacy_loss(
         &mut self,
-        request: PassivePrivacyLossRequest<EI, PureDPBudget>,
+        request: PassivePrivacyLossRequest<EI, QI, FS::Budget>,
     ) -> Result<FilterStatus, ERR> {
-        // For each epoch, try to consume the privacy budget.
-        for epoch_id in request.epoch_ids {
-            self.initialize_filter_if_necessary(&epoch_id)?;
+        let querier_budget = FS::Budget::express_for_capacity(
+            &request.privacy_budget,
+            &self.querier_capacity,
+        );
+        let global_budget = FS::Budget::express_for_capacity(
+            &request.privacy_budget,
+            &self.global_capacity,
+        );
+        self.check_and_consume_epochs(
+            &request.epoch_ids,
+            &request.querier_id,
+            &querier_budget,
+            &global_budget,
+        )
+    }
 
-            // Try to consume budget from current epoch.
-            let filter_status = self
-                .filter_storage
-                .check_and_consume(&epoch_id, &request.privacy_budget)?;
+    /// Consumes `querier_budget` from the querier filter and `global_budget`
+    /// from the global filter of each epoch in `epoch_ids`, initializing them
+    /// if necessary. This is a single transaction: budget is only deducted if
+    /// every filter can afford it, otherwise no filter is modified.
+    fn check_and_consume_epochs(
+        &mut self,
+        epoch_ids: &[EI],
+        querier_id: &QI,
+        querier_budget: &FS::Budget,
+        global_budget: &FS::Budget,
+    ) -> Result<FilterStatus, ERR> {
+        let mut requests = vec![];
+        for epoch_id in epoch_ids {
+            let filter_budgets = [
+                (
+                    FilterId::Querier(epoch_id.clone(), querier_id.clone()),
+                    querier_budget,
+                ),
+                (FilterId::Global(epoch_id.clone()), global_budget),
+            ];
+            for (filter_id, budget) in filter_budgets {
+                self.initialize_filter_if_necessary(&filter_id)?;
+                requests.push((filter_id, budget.clone()));
+            }
+        }
+
+        let filter_status =
+            self.filter_storage.check_and_consume_all(&requests)?;
+        Ok(filter_status)
+    }
 
-            if filter_status == FilterStatus::OutOfBudget {
+    /// Checks whether the querier filter and the global filter of `epoch_id`
+    /// can afford `querier_budget` and `global_budget`, initializing them if
+    /// necessary. Doesn't consume any budget.
+    fn can_consume_epoch(
+        &mut self,
+        epoch_id: &EI,
+        querier_id: &QI,
+        querier_budget: &FS::Budget,
+        global_budget: &FS::Budget,
+    ) -> Result<FilterStatus, ERR> {
+        let filter_budgets = [
+            (
+                FilterId::Querier(epoch_id.clone(), querier_id.clone()),
+                querier_budget,
+            ),
+            (FilterId::Global(epoch_id.clone()), global_budget),
+        ];
+        for (filter_id, budget) in filter_budgets {
+            self.initialize_filter_if_necessary(&filter_id)?;
+            if self.filter_storage.can_consume(&filter_id, budget)?
+                == FilterStatus::OutOfBudget
+            {
                 return Ok(FilterStatus::OutOfBudget);
             }
-
-            // TODO(https://github.com/columbia/pdslib/issues/16): semantics are still unclear, for now we ignore the request if
-            // it would exhaust the filter.
         }
         Ok(FilterStatus::Continue)
     }
 
+    /// Drops the epochs that can't pay their leave-one-epoch-out loss from
+    /// `relevant_events_per_epoch`, and returns the sensitivities of the
+    /// remaining epochs. Dropping an epoch changes the report, and thus the
+    /// sensitivities of the other epochs, so they are recomputed until no
+    /// epoch is dropped. Doesn't consume any budget.
+    fn drop_epochs_for_leave_one_epoch_out(
+        &mut self,
+        request: &Q,
+        relevant_events_per_epoch: &mut RelevantEventsPerEpoch<EI, EE>,
+        num_epochs: usize,
+    ) -> Result<HashMap<EI, f64>, ERR> {
+        let querier_id = request.get_querier_id();
+        loop {
+            let report = request.compute_report(relevant_events_per_epoch);
+            let sensitivities = compute_leave_one_epoch_out_sensitivities(
+                request,
+                relevant_events_per_epoch,
+                &report,
+            );
+
+            let mut out_of_budget_epochs = vec![];
+            for (epoch_id, epoch_relevant_events) in
+                relevant_events_per_epoch.iter()
+            {
+                let (querier_loss, global_loss) = self
+                    .compute_individual_privacy_loss(
+                        request,
+                        Some(epoch_relevant_events),
+                        &report,
+                        num_epochs,
+                        sensitivities.get(epoch_id).copied(),
+                    );
+                let status = self.can_consume_epoch(
+                    epoch_id,
+                    &querier_id,
+                    &querier_loss,
+                    &global_loss,
+                )?;
+                if status == FilterStatus::OutOfBudget {
+                    out_of_budget_epochs.push(epoch_id.clone());
+                }
+            }
+
+            if out_of_budget_epochs.is_empty() {
+                return Ok(sensitivities);
+            }
+            for epoch_id in out_of_budget_epochs {
+                relevant_events_per_epoch.remove(&epoch_id);
+            }
+        }
+    }
+
     fn initialize_filter_if_necessary(
         &mut self,
-        epoch_id: &EI,
+        filter_id: &FilterId<EI, QI>,
     ) -> Result<(), ERR> {
         let filter_initialized =
-            self.filter_storage.is_initialized(epoch_id)?;
+            self.filter_storage.is_initialized(filter_id)?;
 
         if !filter_initialized {
-            let create_filter_result = self
-                .filter_storage
-                .new_filter(epoch_id.clone(), self.epoch_capacity.clone());
+            let capacity = match filter_id {
+                FilterId::Querier(..) => self.querier_capacity.clone(),
+                FilterId::Global(..) => self.global_capacity.clone(),
+            };
+            let create_filter_result =
+                self.filter_storage.new_filter(filter_id.clone(), capacity);
 
             if create_filter_result.is_err() {
                 return Ok(());
@@ -191,59 +506,104 @@ where
         Ok(())
     }
 
-    /// Pure DP individual privacy loss, following
-    /// `compute_individual_privacy_loss` from Code Listing 1 in Cookie Monster (https://arxiv.org/pdf/2405.16719).
-    ///
-    /// TODO(https://github.com/columbia/pdslib/issues/21): generic budget.
+    /// Individual privacy loss, following `compute_individual_privacy_loss`
+    /// from Code Listing 1 in Cookie Monster (https://arxiv.org/pdf/2405.16719).
+    /// The sensitivity is converted into a budget by the filters' budget
+    /// type. Returns the losses for the querier filter and for the global
+    /// filter, each computed for its own capacity, e.g. for its RDP orders.
     fn compute_individual_privacy_loss(
         &self,
         request: &Q,
         epoch_relevant_events: Option<&EE>,
         computed_attribution: &<Q as ReportRequest>::Report,
         num_epochs: usize,
-    ) -> PureDPBudget {
-        // Case 1: Epoch with no relevant events
-        match epoch_relevant_events {
-            None => {
-                return PureDPBudget::Epsilon(0.0);
-            }
-            Some(epoch_events) => {
-                if epoch_events.is_empty() {
-                    return PureDPBudget::Epsilon(0.0);
-                }
-            }
-        }
+        leave_one_epoch_out_sensitivity: Option<f64>,
+    ) -> (FS::Budget, FS::Budget) {
+        let noise_scale = request.get_noise_scale();
+        let individual_sensitivity = match epoch_relevant_events {
+            // Case 1: Epoch with no relevant events.
+            None => 0.0,
+            Some(epoch_events) if epoch_events.is_empty() => 0.0,
 
-        let individual_sensitivity = match num_epochs {
-            1 => {
-                // Case 2: One epoch.
-                request.get_single_epoch_individual_sensitivity(
+            // Case 2: One epoch. The sensitivity is measured in the norm that
+            // matches the noise, e.g. L2 for Gaussian noise.
+            Some(_) if num_epochs == 1 => request
+                .get_single_epoch_individual_sensitivity(
                     computed_attribution,
-                    NormType::L1,
-                )
-            }
-            _ => {
-                // Case 3: Multiple epochs.
-                request.get_report_global_sensitivity()
-            }
+                    noise_scale.get_norm_type(),
+                ),
+
+            // Case 3: Multiple epochs. The global sensitivity bounds the L1
+            // norm, so it also bounds the L2 norm. The leave-one-epoch-out
+            // sensitivity is only available if it was requested.
+            Some(_) => leave_one_epoch_out_sensitivity
+                .unwrap_or_else(|| request.get_report_global_sensitivity()),
+        };
+
+        // With declared privacy parameters, the loss is computed from them as
+        // in Cookie Monster, i.e. `individual_sensitivity * requested_epsilon
+        // / global_sensitivity`, for the same mechanism with unit scale. The
+        // noise scale was already validated against the parameters.
+        let (individual_sensitivity, noise_scale) =
+            match request.get_requested_privacy_parameters() {
+                Some(privacy_parameters) => {
+                    let individual_epsilon = privacy_parameters
+                        .get_individual_epsilon(individual_sensitivity);
+                    let unit_noise_scale = match noise_scale {
+                        NoiseScale::RandomizedResponse(_) => {
+                            NoiseScale::RandomizedResponse(1.0)
+                        }
+                        _ => NoiseScale::Laplace(1.0),
+                    };
+                    (individual_epsilon, unit_noise_scale)
+                }
+                None => (individual_sensitivity, noise_scale),
+            };
+        let loss = |capacity| {
+            FS::Budget::compute_privacy_loss(
+                individual_sensitivity,
+                &noise_scale,
+                capacity,
+            )
         };
+        (loss(&self.querier_capacity), loss(&self.global_capacity))
+    }
+}
 
-        let NoiseScale::Laplace(noise_scale) = request.get_noise_scale();
+/// Returns, for each epoch with relevant events, the distance between `report`
+/// and the report computed without the events of that epoch, capped by the
+/// global sensitivity. Epochs are missing if the request can't compare
+/// reports.
+fn compute_leave_one_epoch_out_sensitivities<Q: EpochReportRequest>(
+    request: &Q,
+    relevant_events_per_epoch: &mut RelevantEventsPerEpoch<
+        Q::EpochId,
+        Q::EpochEvents,
+    >,
+    report: &Q::Report,
+) -> HashMap<Q::EpochId, f64> {
+    let norm_type = request.get_noise_scale().get_norm_type();
+    let global_sensitivity = request.get_report_global_sensitivity();
+    let epoch_ids: Vec<Q::EpochId> = relevant_events_per_epoch
+        .iter()
+        .map(|(epoch_id, _)| epoch_id.clone())
+        .collect();
 
-        // Treat near-zero noise scales as non-private, i.e. requesting infinite
-        // budget, which can only go through if filters are also set to
-        // infinite capacity, e.g. for debugging. The machine precision
-        // `f64::EPSILON` is not related to privacy.
-        if noise_scale.abs() < f64::EPSILON {
-            return PureDPBudget::Infinite;
+    let mut sensitivities = HashMap::new();
+    for epoch_id in epoch_ids {
+        let report_without_epoch = relevant_events_per_epoch
+            .with_epoch_removed(&epoch_id, |relevant_events_per_epoch| {
+                request.compute_report(relevant_events_per_epoch)
+            });
+        if let Some(distance) = request.get_report_distance(
+            report,
+            &report_without_epoch,
+            norm_type,
+        ) {
+            sensitivities.insert(epoch_id, distance.min(global_sensitivity));
         }
-
-        // In Cookie Monster, we have `query_global_sensitivity` /
-        // `requested_epsilon` instead of just `noise_scale`.
-        // TODO(https://github.com/columbia/pdslib/issues/23): potentially use two parameters
-        // instead of a single `noise_scale`.
-        PureDPBudget::Epsilon(individual_sensitivity / noise_scale)
     }
+    sensitivities
 }
 
 #[cfg(test)]
@@ -253,35 +613,65 @@ mod tests {
         budget::{
             hashmap_filter_storage::HashMapFilterStorage,
             pure_dp_filter::{PureDPBudget, PureDPBudgetFilter},
+            rdp_filter::{RdpBudget, RdpBudgetFilter},
+            zcdp_filter::{ZcdpBudget, ZcdpBudgetFilter},
         },
-        events::hashmap_event_storage::HashMapEventStorage,
+        events::{
+            epoch_clock::EpochClock,
+            hashmap_event_storage::HashMapEventStorage,
+            simple_event::SimpleEvent,
+        },
+        mechanisms::NoiseScale,
+        pds::hashmap_deduplication_key_storage::HashMapDeduplicationKeyStorage,
         queries::{
-            simple_last_touch_histogram::SimpleLastTouchHistogramRequest,
+            simple_last_touch_histogram::{
+                SimpleLastTouchHistogramRequest, SimpleRelevantEventSelector,
+            },
             traits::PassivePrivacyLossRequest,
         },
     };
 
+    type PureDPFilters = HashMapFilterStorage<
+        FilterId<usize, usize>,
+        PureDPBudgetFilter,
+        PureDPBudget,
+    >;
+
+    type Pds<FS> = EpochPrivateDataService<
+        FS,
+        HashMapEventStorage<SimpleEvent, SimpleRelevantEventSelector>,
+        HashMapDeduplicationKeyStorage<usize, usize>,
+        SimpleLastTouchHistogramRequest,
+        anyhow::Error,
+    >;
+
+    /// Epochs of 100 seconds.
+    fn epoch_clock() -> EpochClock {
+        EpochClock::new(100).unwrap()
+    }
+
+    /// Event at the start of `epoch_number`.
+    fn event(id: usize, epoch_number: usize, event_key: usize) -> SimpleEvent {
+        let clock = epoch_clock();
+        let timestamp = clock.get_epoch_start(epoch_number);
+        SimpleEvent::new(id, timestamp, event_key, &clock)
+    }
+
     #[test]
     fn test_account_for_passive_privacy_loss() {
-        let filters: HashMapFilterStorage<
-            usize,
-            PureDPBudgetFilter,
-            PureDPBudget,
-        > = HashMapFilterStorage::new();
-        let events = HashMapEventStorage::new();
-
-        let mut pds = EpochPrivateDataService {
-            filter_storage: filters,
-            event_storage: events,
-            epoch_capacity: PureDPBudget::Epsilon(3.0),
-            _phantom_request: std::marker::PhantomData::<
-                SimpleLastTouchHistogramRequest,
-            >,
-            _phantom_error: std::marker::PhantomData::<anyhow::Error>,
-        };
+        let mut pds: Pds<_> = EpochPrivateDataService::new(
+            PureDPFilters::new(),
+            HashMapEventStorage::new(),
+            HashMapDeduplicationKeyStorage::new(),
+            epoch_clock(),
+            PureDPBudget::Epsilon(3.0),
+            PureDPBudget::Infinite,
+        );
+        let filter_id = |epoch_id| FilterId::Querier(epoch_id, 1);
 
         // First request should succeed
         let request = PassivePrivacyLossRequest {
+            querier_id: 1,
             epoch_ids: vec![1, 2, 3],
             privacy_budget: PureDPBudget::Epsilon(1.0),
         };
@@ -290,6 +680,7 @@ mod tests {
 
         // Second request with same budget should succeed (2.0 total)
         let request = PassivePrivacyLossRequest {
+            querier_id: 1,
             epoch_ids: vec![1, 2, 3],
             privacy_budget: PureDPBudget::Epsilon(1.0),
         };
@@ -300,7 +691,7 @@ mod tests {
         for epoch_id in 1..=3 {
             let remaining = pds
                 .filter_storage
-                .get_remaining_budget(&epoch_id)
+                .get_remaining_budget(&filter_id(epoch_id))
                 .expect("Failed to get remaining budget");
             assert_eq!(remaining, PureDPBudget::Epsilon(1.0)); // 3.0 - 2.0 =
                                                                // 1.0 remaining
@@ -308,6 +699,7 @@ mod tests {
 
         // Attempting to consume more should fail.
         let request = PassivePrivacyLossRequest {
+            querier_id: 1,
             epoch_ids: vec![2, 3],
             privacy_budget: PureDPBudget::Epsilon(2.0),
         };
@@ -316,24 +708,491 @@ mod tests {
 
         // Consume from just one epoch.
         let request = PassivePrivacyLossRequest {
+            querier_id: 1,
             epoch_ids: vec![3],
             privacy_budget: PureDPBudget::Epsilon(1.0),
         };
         let result = pds.account_for_passive_privacy_loss(request).unwrap();
         assert_eq!(result, FilterStatus::Continue);
 
+        // Epoch 1 could afford this request but epoch 3 cannot, so the whole
+        // request is rejected and epoch 1 is left untouched.
+        let request = PassivePrivacyLossRequest {
+            querier_id: 1,
+            epoch_ids: vec![1, 3],
+            privacy_budget: PureDPBudget::Epsilon(1.0),
+        };
+        let result = pds.account_for_passive_privacy_loss(request).unwrap();
+        assert_eq!(result, FilterStatus::OutOfBudget);
+
         // Verify remaining budgets
         for epoch_id in 1..=2 {
             let remaining = pds
                 .filter_storage
-                .get_remaining_budget(&epoch_id)
+                .get_remaining_budget(&filter_id(epoch_id))
                 .expect("Failed to get remaining budget");
             assert_eq!(remaining, PureDPBudget::Epsilon(1.0));
         }
         let remaining = pds
             .filter_storage
-            .get_remaining_budget(&3)
+            .get_remaining_budget(&filter_id(3))
             .expect("Failed to get remaining budget");
         assert_eq!(remaining, PureDPBudget::Epsilon(0.0));
     }
+
+    #[test]
+    fn test_register_event_checks_epoch() {
+        let mut pds: Pds<_> = EpochPrivateDataService::new(
+            PureDPFilters::new(),
+            HashMapEventStorage::new(),
+            HashMapDeduplicationKeyStorage::new(),
+            epoch_clock(),
+            PureDPBudget::Epsilon(1.0),
+            PureDPBudget::Infinite,
+        );
+
+        // Epoch 1 covers [100, 200), so an event at 250 can't be in it.
+        let event = SimpleEvent {
+            id: 1,
+            epoch_number: 1,
+            timestamp: 250,
+            event_key: 3,
+        };
+        assert!(pds.register_event(event).is_err());
+
+        let event = SimpleEvent::new(1, 250, 3, &pds.epoch_clock);
+        pds.register_event(event).unwrap();
+        let request = SimpleLastTouchHistogramRequest {
+            epoch_start: 1,
+            epoch_end: 2,
+            attributable_value: 1.0,
+            laplace_noise_scale: 1.0,
+            privacy_parameters: None,
+            querier_id: 1,
+            is_relevant_event: |_| true,
+        };
+        let report = pds.compute_report(request).unwrap();
+        assert_eq!(report.bin_value, Some((3, 1.0)));
+    }
+
+    #[test]
+    fn test_leave_one_epoch_out_sensitivity() {
+        let mut pds: Pds<_> = EpochPrivateDataService::new(
+            PureDPFilters::new(),
+            HashMapEventStorage::new(),
+            HashMapDeduplicationKeyStorage::new(),
+            epoch_clock(),
+            PureDPBudget::Epsilon(3.0),
+            PureDPBudget::Infinite,
+        );
+
+        // One event in each of epochs 1, 2 and 3, with different keys.
+        for epoch_number in 1..=3 {
+            let event = event(epoch_number, epoch_number, epoch_number);
+            pds.register_event(event).unwrap();
+        }
+
+        let request = |querier_id| SimpleLastTouchHistogramRequest {
+            epoch_start: 1,
+            epoch_end: 3,
+            attributable_value: 1.0,
+            laplace_noise_scale: 1.0,
+            privacy_parameters: None,
+            querier_id,
+            is_relevant_event: |_| true,
+        };
+        let remaining = |filters: &PureDPFilters, querier_id| {
+            (1..=3)
+                .map(|epoch_id| {
+                    filters
+                        .get_remaining_budget(&FilterId::Querier(
+                            epoch_id, querier_id,
+                        ))
+                        .unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Case 3 charges the global sensitivity to every epoch.
+        let report = pds.compute_report(request(1)).unwrap();
+        assert_eq!(report.bin_value, Some((3, 1.0)));
+        assert_eq!(
+            remaining(&pds.filter_storage, 1),
+            vec![PureDPBudget::Epsilon(2.0); 3]
+        );
+
+        // Epoch 3 holds the last touch, so removing epoch 1 or 2 doesn't
+        // change the report and costs nothing. Removing epoch 3 moves the
+        // value to another bin, which costs the global sensitivity.
+        pds.multi_epoch_sensitivity = MultiEpochSensitivity::LeaveOneEpochOut;
+        let report = pds.compute_report(request(2)).unwrap();
+        assert_eq!(report.bin_value, Some((3, 1.0)));
+        assert_eq!(
+            remaining(&pds.filter_storage, 2),
+            vec![
+                PureDPBudget::Epsilon(3.0),
+                PureDPBudget::Epsilon(3.0),
+                PureDPBudget::Epsilon(2.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_leave_one_epoch_out_sensitivity_after_dropped_epochs() {
+        let mut pds: Pds<_> = EpochPrivateDataService::new(
+            PureDPFilters::new(),
+            HashMapEventStorage::new(),
+            HashMapDeduplicationKeyStorage::new(),
+            epoch_clock(),
+            PureDPBudget::Epsilon(1.0),
+            PureDPBudget::Infinite,
+        );
+        pds.multi_epoch_sensitivity = MultiEpochSensitivity::LeaveOneEpochOut;
+        for epoch_number in 1..=3 {
+            let event = event(epoch_number, epoch_number, epoch_number);
+            pds.register_event(event).unwrap();
+        }
+
+        // Epoch 3 is exhausted by passive privacy loss.
+        let passive_request = PassivePrivacyLossRequest {
+            querier_id: 1,
+            epoch_ids: vec![3],
+            privacy_budget: PureDPBudget::Epsilon(1.0),
+        };
+        pds.account_for_passive_privacy_loss(passive_request)
+            .unwrap();
+
+        let request = SimpleLastTouchHistogramRequest {
+            epoch_start: 1,
+            epoch_end: 3,
+            attributable_value: 1.0,
+            laplace_noise_scale: 1.0,
+            privacy_parameters: None,
+            querier_id: 1,
+            is_relevant_event: |_| true,
+        };
+        let remaining = |filters: &PureDPFilters, epoch_id| {
+            filters
+                .get_remaining_budget(&FilterId::Querier(epoch_id, 1))
+                .unwrap()
+        };
+
+        // Without epoch 3, the report depends on epoch 2, which pays for it.
+        let report = pds.compute_report(request.clone()).unwrap();
+        assert_eq!(report.bin_value, Some((2, 1.0)));
+        assert_eq!(
+            remaining(&pds.filter_storage, 2),
+            PureDPBudget::Epsilon(0.0)
+        );
+        assert_eq!(
+            remaining(&pds.filter_storage, 1),
+            PureDPBudget::Epsilon(1.0)
+        );
+
+        // Epoch 2 is now exhausted too, so the report moves to epoch 1.
+        let report = pds.compute_report(request.clone()).unwrap();
+        assert_eq!(report.bin_value, Some((1, 1.0)));
+        assert_eq!(
+            remaining(&pds.filter_storage, 1),
+            PureDPBudget::Epsilon(0.0)
+        );
+
+        let report = pds.compute_report(request).unwrap();
+        assert_eq!(report.bin_value, None);
+    }
+
+    #[test]
+    fn test_querier_filters_are_isolated() {
+        let mut pds: Pds<_> = EpochPrivateDataService::new(
+            PureDPFilters::new(),
+            HashMapEventStorage::new(),
+            HashMapDeduplicationKeyStorage::new(),
+            epoch_clock(),
+            PureDPBudget::Epsilon(1.0),
+            PureDPBudget::Infinite,
+        );
+
+        let event = event(1, 1, 3);
+        pds.register_event(event).unwrap();
+
+        let request = |querier_id| SimpleLastTouchHistogramRequest {
+            epoch_start: 1,
+            epoch_end: 1,
+            attributable_value: 1.0,
+            laplace_noise_scale: 1.0,
+            privacy_parameters: None,
+            querier_id,
+            is_relevant_event: |_| true,
+        };
+
+        // Querier 1 exhausts its budget for epoch 1.
+        let report = pds.compute_report(request(1)).unwrap();
+        assert_eq!(report.bin_value, Some((3, 1.0)));
+        let report = pds.compute_report(request(1)).unwrap();
+        assert_eq!(report.bin_value, None);
+
+        // Querier 2 still has its own budget for epoch 1.
+        let report = pds.compute_report(request(2)).unwrap();
+        assert_eq!(report.bin_value, Some((3, 1.0)));
+
+        for querier_id in [1, 2] {
+            let remaining = pds
+                .filter_storage
+                .get_remaining_budget(&FilterId::Querier(1, querier_id))
+                .unwrap();
+            assert_eq!(remaining, PureDPBudget::Epsilon(0.0));
+        }
+    }
+
+    #[test]
+    fn test_clearing_events_does_not_refund_budget() {
+        let mut pds: Pds<_> = EpochPrivateDataService::new(
+            PureDPFilters::new(),
+            HashMapEventStorage::new(),
+            HashMapDeduplicationKeyStorage::new(),
+            epoch_clock(),
+            PureDPBudget::Epsilon(1.5),
+            PureDPBudget::Infinite,
+        );
+
+        for (id, epoch_number) in [(1, 1), (2, 2), (3, 3)] {
+            let event = event(id, epoch_number, id);
+            pds.register_event(event).unwrap();
+        }
+
+        let request = |epoch| SimpleLastTouchHistogramRequest {
+            epoch_start: epoch,
+            epoch_end: epoch,
+            attributable_value: 1.0,
+            laplace_noise_scale: 1.0,
+            privacy_parameters: None,
+            querier_id: 1,
+            is_relevant_event: |_| true,
+        };
+        let report = pds.compute_report(request(1)).unwrap();
+        assert_eq!(report.bin_value, Some((1, 1.0)));
+
+        // Deleting by epoch range or by predicate only removes events.
+        pds.clear_epochs(1..=2).unwrap();
+        assert_eq!(pds.compute_report(request(1)).unwrap().bin_value, None);
+        assert_eq!(pds.compute_report(request(2)).unwrap().bin_value, None);
+        pds.clear_events(|event| event.id == 3).unwrap();
+        assert_eq!(pds.compute_report(request(3)).unwrap().bin_value, None);
+
+        // The budget consumed before the deletion is not refunded: events
+        // registered again in epoch 1 can't be reported twice.
+        assert_eq!(
+            pds.filter_storage
+                .get_remaining_budget(&FilterId::Querier(1, 1))
+                .unwrap(),
+            PureDPBudget::Epsilon(0.5)
+        );
+        let event = event(4, 1, 4);
+        pds.register_event(event).unwrap();
+        assert_eq!(pds.compute_report(request(1)).unwrap().bin_value, None);
+
+        pds.clear_all_events().unwrap();
+        assert_eq!(
+            pds.filter_storage
+                .get_remaining_budget(&FilterId::Querier(1, 1))
+                .unwrap(),
+            PureDPBudget::Epsilon(0.5)
+        );
+    }
+
+    #[test]
+    fn test_global_filter_bounds_all_queriers() {
+        let mut pds: Pds<_> = EpochPrivateDataService::new(
+            PureDPFilters::new(),
+            HashMapEventStorage::new(),
+            HashMapDeduplicationKeyStorage::new(),
+            epoch_clock(),
+            PureDPBudget::Epsilon(1.0),
+            PureDPBudget::Epsilon(1.5),
+        );
+
+        let event = event(1, 1, 3);
+        pds.register_event(event).unwrap();
+
+        let request = |querier_id| SimpleLastTouchHistogramRequest {
+            epoch_start: 1,
+            epoch_end: 1,
+            attributable_value: 1.0,
+            laplace_noise_scale: 1.0,
+            privacy_parameters: None,
+            querier_id,
+            is_relevant_event: |_| true,
+        };
+
+        // Querier 1 consumes from its own filter and from the global filter.
+        let report = pds.compute_report(request(1)).unwrap();
+        assert_eq!(report.bin_value, Some((3, 1.0)));
+        assert_eq!(
+            pds.filter_storage
+                .get_remaining_budget(&FilterId::Global(1))
+                .unwrap(),
+            PureDPBudget::Epsilon(0.5)
+        );
+
+        // Querier 2 has enough budget in its own filter, but the global filter
+        // rejects the request.
+        let report = pds.compute_report(request(2)).unwrap();
+        assert_eq!(report.bin_value, None);
+
+        // Nothing was deducted from either filter.
+        assert_eq!(
+            pds.filter_storage
+                .get_remaining_budget(&FilterId::Querier(1, 2))
+                .unwrap(),
+            PureDPBudget::Epsilon(1.0)
+        );
+        assert_eq!(
+            pds.filter_storage
+                .get_remaining_budget(&FilterId::Global(1))
+                .unwrap(),
+            PureDPBudget::Epsilon(0.5)
+        );
+
+        // Passive privacy loss is also bounded by the global filter.
+        let request = PassivePrivacyLossRequest {
+            epoch_ids: vec![1],
+            querier_id: 2,
+            privacy_budget: PureDPBudget::Epsilon(0.5),
+        };
+        let result = pds.account_for_passive_privacy_loss(request).unwrap();
+        assert_eq!(result, FilterStatus::Continue);
+        assert_eq!(
+            pds.filter_storage
+                .get_remaining_budget(&FilterId::Global(1))
+                .unwrap(),
+            PureDPBudget::Epsilon(0.0)
+        );
+    }
+
+    #[test]
+    fn test_generic_budgets() {
+        let request = SimpleLastTouchHistogramRequest {
+            epoch_start: 1,
+            epoch_end: 1,
+            attributable_value: 1.0,
+            laplace_noise_scale: 1.0,
+            privacy_parameters: None,
+            querier_id: 1,
+            is_relevant_event: |_| true,
+        };
+        let event = event(1, 1, 3);
+
+        // zCDP filters: a report with epsilon = 1 costs rho = 1/2.
+        let filters: HashMapFilterStorage<
+            FilterId<usize, usize>,
+            ZcdpBudgetFilter,
+            ZcdpBudget,
+        > = HashMapFilterStorage::new();
+        let mut pds: Pds<_> = EpochPrivateDataService::new(
+            filters,
+            HashMapEventStorage::new(),
+            HashMapDeduplicationKeyStorage::new(),
+            epoch_clock(),
+            ZcdpBudget::Rho(1.0),
+            ZcdpBudget::Infinite,
+        );
+        pds.register_event(event.clone()).unwrap();
+        let report = pds.compute_report(request.clone()).unwrap();
+        assert_eq!(report.bin_value, Some((3, 1.0)));
+        assert_eq!(
+            pds.filter_storage
+                .get_remaining_budget(&FilterId::Querier(1, 1))
+                .unwrap(),
+            ZcdpBudget::Rho(0.5)
+        );
+
+        // RDP filters: losses are computed for the orders of each capacity,
+        // even if the querier and global filters use different orders.
+        let filters: HashMapFilterStorage<
+            FilterId<usize, usize>,
+            RdpBudgetFilter,
+            RdpBudget,
+        > = HashMapFilterStorage::new();
+        let capacity = RdpBudget {
+            alpha_epsilons: vec![(2.0, 1.0), (8.0, 1.0)],
+        };
+        let mut pds: Pds<_> = EpochPrivateDataService::new(
+            filters,
+            HashMapEventStorage::new(),
+            HashMapDeduplicationKeyStorage::new(),
+            epoch_clock(),
+            capacity.clone(),
+            RdpBudget {
+                alpha_epsilons: vec![(4.0, 10.0)],
+            },
+        );
+        pds.register_event(event).unwrap();
+        let report = pds.compute_report(request).unwrap();
+        assert_eq!(report.bin_value, Some((3, 1.0)));
+
+        let loss = RdpBudget::compute_privacy_loss(
+            1.0,
+            &NoiseScale::Laplace(1.0),
+            &capacity,
+        );
+        let remaining = pds
+            .filter_storage
+            .get_remaining_budget(&FilterId::Querier(1, 1))
+            .unwrap();
+        for ((_, r), ((_, c), (_, l))) in remaining
+            .alpha_epsilons
+            .iter()
+            .zip(capacity.alpha_epsilons.iter().zip(&loss.alpha_epsilons))
+        {
+            assert_eq!(*r, c - l);
+            assert!(*l > 0.0 && *l < 1.0);
+        }
+
+        let global_loss = RdpBudget::compute_privacy_loss(
+            1.0,
+            &NoiseScale::Laplace(1.0),
+            &pds.global_capacity,
+        );
+        assert_eq!(
+            pds.filter_storage
+                .get_remaining_budget(&FilterId::Global(1))
+                .unwrap(),
+            RdpBudget {
+                alpha_epsilons: vec![(
+                    4.0,
+                    10.0 - global_loss.alpha_epsilons[0].1
+                )],
+            }
+        );
+
+        // Passive losses are also expressed for the orders of each capacity.
+        let passive_request = PassivePrivacyLossRequest {
+            querier_id: 1,
+            epoch_ids: vec![2],
+            privacy_budget: RdpBudget {
+                alpha_epsilons: vec![(2.0, 0.1), (8.0, 0.4)],
+            },
+        };
+        assert_eq!(
+            pds.account_for_passive_privacy_loss(passive_request)
+                .unwrap(),
+            FilterStatus::Continue
+        );
+        assert_eq!(
+            pds.filter_storage
+                .get_remaining_budget(&FilterId::Querier(2, 1))
+                .unwrap(),
+            RdpBudget {
+                alpha_epsilons: vec![(2.0, 1.0 - 0.1), (8.0, 1.0 - 0.4)],
+            }
+        );
+        assert_eq!(
+            pds.filter_storage
+                .get_remaining_budget(&FilterId::Global(2))
+                .unwrap(),
+            RdpBudget {
+                alpha_epsilons: vec![(4.0, 10.0 - 0.4)],
+            }
+        );
+    }
 }
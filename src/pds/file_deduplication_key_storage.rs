@@ -0,0 +1,180 @@
+use std::{
+    fs::{self, File},
+    hash::Hash,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::pds::{
+    hashmap_deduplication_key_storage::HashMapDeduplicationKeyStorage,
+    traits::DeduplicationKeyStorage,
+};
+
+/// DeduplicationKeyStorage that persists keys to a local JSON file, so that
+/// a querier can't get a second report for a key after a restart. Keys are
+/// kept in memory with a `HashMapDeduplicationKeyStorage`, and written to
+/// disk after every modification, with the same crash-consistent writes as
+/// `FileFilterStorage`.
+///
+/// A key whose write fails returns an error: the PDS then doesn't release
+/// the report, so the key can be reused after a restart.
+pub struct FileDeduplicationKeyStorage<EI, QI> {
+    path: PathBuf,
+    keys: HashMapDeduplicationKeyStorage<EI, QI>,
+}
+
+impl<EI, QI> FileDeduplicationKeyStorage<EI, QI>
+where
+    EI: Serialize + DeserializeOwned,
+    QI: Eq + Hash + Serialize + DeserializeOwned,
+{
+    /// Opens the keys persisted at `path`, or creates an empty storage if the
+    /// file does not exist yet. Leftovers from writes that were interrupted
+    /// by a crash are discarded.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref().to_path_buf();
+
+        let tmp_path = Self::tmp_path(&path);
+        if tmp_path.exists() {
+            fs::remove_file(&tmp_path)
+                .context("Failed to remove interrupted write")?;
+        }
+
+        let keys = if path.exists() {
+            let file = File::open(&path)
+                .context("Failed to open deduplication keys")?;
+            let entries: Vec<((QI, u64), EI)> =
+                serde_json::from_reader(BufReader::new(file))
+                    .context("Failed to parse deduplication keys")?;
+            entries.into_iter().collect()
+        } else {
+            HashMapDeduplicationKeyStorage::new()
+        };
+
+        Ok(Self { path, keys })
+    }
+
+    fn tmp_path(path: &Path) -> PathBuf {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        PathBuf::from(tmp_path)
+    }
+
+    /// Atomically replaces the file with the current set of keys.
+    fn persist(&self) -> Result<(), anyhow::Error> {
+        let tmp_path = Self::tmp_path(&self.path);
+        let entries: Vec<(&(QI, u64), &EI)> = self.keys.iter().collect();
+
+        let file = File::create(&tmp_path)
+            .context("Failed to create temporary deduplication keys file")?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, &entries)
+            .context("Failed to serialize deduplication keys")?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+
+        fs::rename(&tmp_path, &self.path)
+            .context("Failed to replace deduplication keys file")?;
+
+        // Sync the directory too, so the rename itself is durable.
+        if let Some(dir) = self.path.parent() {
+            if let Ok(dir) = File::open(dir) {
+                let _ = dir.sync_all();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<EI, QI> DeduplicationKeyStorage for FileDeduplicationKeyStorage<EI, QI>
+where
+    EI: Serialize + DeserializeOwned,
+    QI: Eq + Hash + Clone + Serialize + DeserializeOwned,
+{
+    type EpochId = EI;
+    type QuerierId = QI;
+    type Error = anyhow::Error;
+
+    fn add_key(
+        &mut self,
+        epoch_id: EI,
+        querier_id: QI,
+        deduplication_key: u64,
+    ) -> Result<(), Self::Error> {
+        self.keys.add_key(epoch_id, querier_id, deduplication_key)?;
+        self.persist()
+    }
+
+    fn contains_key(
+        &self,
+        querier_id: &QI,
+        deduplication_key: u64,
+    ) -> Result<bool, Self::Error> {
+        self.keys.contains_key(querier_id, deduplication_key)
+    }
+
+    fn delete_keys(
+        &mut self,
+        predicate: impl Fn(&EI) -> bool,
+    ) -> Result<(), Self::Error> {
+        if self.keys.iter().all(|(_, epoch_id)| !predicate(epoch_id)) {
+            return Ok(());
+        }
+        self.keys.delete_keys(predicate)?;
+        self.persist()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Storage = FileDeduplicationKeyStorage<usize, String>;
+
+    #[test]
+    fn test_file_deduplication_key_storage_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("deduplication_keys.json");
+        let querier_id = "https://ad-tech.example".to_string();
+
+        let mut storage = Storage::open(&path).unwrap();
+        storage.add_key(1, querier_id.clone(), 7).unwrap();
+        storage.add_key(2, querier_id.clone(), 8).unwrap();
+        drop(storage);
+
+        // Keys survive a restart.
+        let mut storage = Storage::open(&path).unwrap();
+        assert!(storage.contains_key(&querier_id, 7).unwrap());
+        assert!(storage.contains_key(&querier_id, 8).unwrap());
+
+        // So do deletions.
+        storage.delete_keys(|epoch_id| *epoch_id < 2).unwrap();
+        drop(storage);
+        let storage = Storage::open(&path).unwrap();
+        assert!(!storage.contains_key(&querier_id, 7).unwrap());
+        assert!(storage.contains_key(&querier_id, 8).unwrap());
+    }
+
+    #[test]
+    fn test_file_deduplication_key_storage_crash_mid_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("deduplication_keys.json");
+        let querier_id = "https://ad-tech.example".to_string();
+
+        let mut storage = Storage::open(&path).unwrap();
+        storage.add_key(1, querier_id.clone(), 7).unwrap();
+        drop(storage);
+
+        // Simulate a crash in the middle of the next write: the temporary file
+        // is truncated and was never renamed.
+        fs::write(Storage::tmp_path(&path), "[[[\"https://ad").unwrap();
+
+        let storage = Storage::open(&path).unwrap();
+        assert!(storage.contains_key(&querier_id, 7).unwrap());
+        assert!(!storage.contains_key(&querier_id, 8).unwrap());
+        assert!(!Storage::tmp_path(&path).exists());
+    }
+}
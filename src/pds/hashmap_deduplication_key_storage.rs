@@ -0,0 +1,92 @@
+use std::{collections::HashMap, hash::Hash};
+
+use crate::pds::traits::DeduplicationKeyStorage;
+
+/// Simple implementation of DeduplicationKeyStorage using a HashMap from
+/// each (querier, key) pair to the epoch it was recorded in.
+#[derive(Debug, Default)]
+pub struct HashMapDeduplicationKeyStorage<EI, QI> {
+    keys: HashMap<(QI, u64), EI>,
+}
+
+impl<EI, QI> HashMapDeduplicationKeyStorage<EI, QI> {
+    pub fn new() -> Self {
+        Self {
+            keys: HashMap::new(),
+        }
+    }
+
+    /// Iterates over all keys, in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&(QI, u64), &EI)> {
+        self.keys.iter()
+    }
+}
+
+impl<EI, QI: Eq + Hash> FromIterator<((QI, u64), EI)>
+    for HashMapDeduplicationKeyStorage<EI, QI>
+{
+    fn from_iter<I: IntoIterator<Item = ((QI, u64), EI)>>(iter: I) -> Self {
+        Self {
+            keys: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<EI, QI: Eq + Hash + Clone> DeduplicationKeyStorage
+    for HashMapDeduplicationKeyStorage<EI, QI>
+{
+    type EpochId = EI;
+    type QuerierId = QI;
+    type Error = anyhow::Error;
+
+    fn add_key(
+        &mut self,
+        epoch_id: EI,
+        querier_id: QI,
+        deduplication_key: u64,
+    ) -> Result<(), Self::Error> {
+        self.keys.insert((querier_id, deduplication_key), epoch_id);
+        Ok(())
+    }
+
+    fn contains_key(
+        &self,
+        querier_id: &QI,
+        deduplication_key: u64,
+    ) -> Result<bool, Self::Error> {
+        Ok(self
+            .keys
+            .contains_key(&(querier_id.clone(), deduplication_key)))
+    }
+
+    fn delete_keys(
+        &mut self,
+        predicate: impl Fn(&EI) -> bool,
+    ) -> Result<(), Self::Error> {
+        self.keys.retain(|_, epoch_id| !predicate(epoch_id));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashmap_deduplication_key_storage() {
+        let mut storage = HashMapDeduplicationKeyStorage::new();
+        storage.add_key(1, "a", 7).unwrap();
+        storage.add_key(2, "a", 8).unwrap();
+        storage.add_key(2, "b", 7).unwrap();
+
+        assert!(storage.contains_key(&"a", 7).unwrap());
+        assert!(storage.contains_key(&"b", 7).unwrap());
+        assert!(!storage.contains_key(&"b", 8).unwrap());
+
+        // Deleting epoch 1 only drops the keys recorded in it.
+        storage.delete_keys(|epoch_id| *epoch_id < 2).unwrap();
+        assert!(!storage.contains_key(&"a", 7).unwrap());
+        assert!(storage.contains_key(&"a", 8).unwrap());
+        assert!(storage.contains_key(&"b", 7).unwrap());
+    }
+}
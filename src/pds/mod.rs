@@ -1 +1,5 @@
+pub mod accounting;
 pub mod epoch_pds;
+pub mod file_deduplication_key_storage;
+pub mod hashmap_deduplication_key_storage;
+pub mod traits;
@@ -0,0 +1,34 @@
+/// Trait for the deduplication keys of the requests that were already
+/// processed, so that a querier gets at most one report per key.
+///
+/// Each key is recorded with an epoch, and is dropped when that epoch is
+/// deleted, which bounds the number of keys by the number of retained epochs.
+pub trait DeduplicationKeyStorage {
+    type EpochId;
+    type QuerierId;
+    type Error;
+
+    /// Records that `querier_id` got a report for `deduplication_key`, for a
+    /// request in epoch `epoch_id`.
+    fn add_key(
+        &mut self,
+        epoch_id: Self::EpochId,
+        querier_id: Self::QuerierId,
+        deduplication_key: u64,
+    ) -> Result<(), Self::Error>;
+
+    /// Checks whether `querier_id` already got a report for
+    /// `deduplication_key`, in any epoch.
+    fn contains_key(
+        &self,
+        querier_id: &Self::QuerierId,
+        deduplication_key: u64,
+    ) -> Result<bool, Self::Error>;
+
+    /// Deletes the keys recorded in the epochs for which `predicate` returns
+    /// true.
+    fn delete_keys(
+        &mut self,
+        predicate: impl Fn(&Self::EpochId) -> bool,
+    ) -> Result<(), Self::Error>;
+}
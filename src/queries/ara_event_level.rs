@@ -0,0 +1,217 @@
+//! [Experimental] ARA-style event-level reports, that carry a few bits of
+//! trigger data perturbed on device by randomized response. Mirrors https://github.com/WICG/attribution-reporting-api/blob/main/EVENT.md
+
+use crate::{
+    events::{ara_event::AraEvent, hashmap_event_storage::VecEpochEvents},
+    mechanisms::{
+        randomized_response::randomized_response, NoiseScale, NormType,
+    },
+    queries::{
+        ara_histogram::AraRelevantEventSelector,
+        traits::{
+            EpochReportRequest, RelevantEventsPerEpoch, Report, ReportRequest,
+        },
+    },
+};
+
+/// Event-level report. `trigger_data` is `None` if the trigger was not
+/// attributed (null report). The report doesn't identify the attributed
+/// source, since that would not be covered by the randomized response.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AraEventLevelReport {
+    pub trigger_data: Option<u64>,
+}
+
+impl Report for AraEventLevelReport {}
+
+/// ARA trigger that asks for an event-level report. The trigger data is
+/// reported if any relevant source is in the requested epochs, and the output
+/// (trigger data or null report) goes through `trigger_data_cardinality +
+/// 1`-ary randomized response on device.
+#[derive(Debug)]
+pub struct AraEventLevelRequest {
+    pub start_epoch: usize,
+    pub end_epoch: usize,
+    /// Reduced modulo `trigger_data_cardinality`, like in ARA.
+    pub trigger_data: u64,
+    pub trigger_data_cardinality: u64, // E.g. 8 for navigation sources.
+    pub epsilon: f64,
+    pub querier_id: String, // E.g. the reporting origin.
+    pub filters: AraRelevantEventSelector,
+    pub deduplication_key: Option<u64>,
+}
+
+impl AraEventLevelRequest {
+    /// Number of possible trigger data values, at least 1.
+    fn get_cardinality(&self) -> u64 {
+        self.trigger_data_cardinality.max(1)
+    }
+}
+
+impl ReportRequest for AraEventLevelRequest {
+    type Report = AraEventLevelReport;
+}
+
+impl EpochReportRequest for AraEventLevelRequest {
+    type EpochId = usize;
+    type EpochEvents = VecEpochEvents<AraEvent>;
+    type QuerierId = String;
+    type ReportGlobalSensitivity = f64;
+    type RelevantEventSelector = AraRelevantEventSelector;
+
+    fn get_epoch_ids(&self) -> Vec<Self::EpochId> {
+        (self.start_epoch..=self.end_epoch).rev().collect()
+    }
+
+    fn get_querier_id(&self) -> String {
+        self.querier_id.clone()
+    }
+
+    fn get_relevant_event_selector(&self) -> Self::RelevantEventSelector {
+        self.filters.clone()
+    }
+
+    fn compute_report(
+        &self,
+        relevant_events_per_epoch: &RelevantEventsPerEpoch<
+            usize,
+            Self::EpochEvents,
+        >,
+    ) -> Self::Report {
+        let is_attributed = relevant_events_per_epoch
+            .values()
+            .any(|epoch_events| !epoch_events.is_empty());
+        let trigger_data =
+            is_attributed.then(|| self.trigger_data % self.get_cardinality());
+        AraEventLevelReport { trigger_data }
+    }
+
+    /// Any change in a single epoch's events can at most turn an attributed
+    /// report into a null report, which randomized response hides.
+    fn get_single_epoch_individual_sensitivity(
+        &self,
+        report: &Self::Report,
+        _norm_type: NormType,
+    ) -> f64 {
+        if report.trigger_data.is_some() {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn get_report_global_sensitivity(&self) -> f64 {
+        1.0
+    }
+
+    /// Reports either are the same or differ by the sensitivity 1.
+    fn get_report_distance(
+        &self,
+        report: &Self::Report,
+        other_report: &Self::Report,
+        _norm_type: NormType,
+    ) -> Option<f64> {
+        Some(if report == other_report { 0.0 } else { 1.0 })
+    }
+
+    fn get_noise_scale(&self) -> NoiseScale {
+        NoiseScale::RandomizedResponse(self.epsilon)
+    }
+
+    /// Randomized response over the trigger data values and the null report,
+    /// so null reports can also be flipped into fake attributions.
+    fn perturb_report(&self, report: Self::Report) -> Self::Report {
+        let cardinality = self.get_cardinality();
+        let value = report.trigger_data.unwrap_or(cardinality);
+        let value = randomized_response(
+            value,
+            cardinality + 1,
+            self.epsilon,
+            &mut rand::rng(),
+        );
+        AraEventLevelReport {
+            trigger_data: (value < cardinality).then_some(value),
+        }
+    }
+
+    fn get_deduplication_key(&self) -> Option<u64> {
+        self.deduplication_key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::events::{ara_event::SourceType, epoch_clock::EpochClock};
+
+    fn request(trigger_data: u64, epsilon: f64) -> AraEventLevelRequest {
+        AraEventLevelRequest {
+            start_epoch: 1,
+            end_epoch: 2,
+            trigger_data,
+            trigger_data_cardinality: 8,
+            epsilon,
+            querier_id: "https://ad-tech.example".to_string(),
+            filters: AraRelevantEventSelector::default(),
+            deduplication_key: None,
+        }
+    }
+
+    #[test]
+    fn test_event_level_report() {
+        let clock = EpochClock::new(100).unwrap();
+        let source = AraEvent::new(
+            1,
+            HashMap::new(),
+            SourceType::Navigation,
+            clock.get_epoch_start(1),
+            &clock,
+        );
+        let relevant_events_per_epoch =
+            RelevantEventsPerEpoch::from_iter([(1, vec![source])]);
+
+        let report = request(3, 1.0).compute_report(&relevant_events_per_epoch);
+        assert_eq!(report.trigger_data, Some(3));
+        assert_eq!(
+            request(3, 1.0)
+                .get_single_epoch_individual_sensitivity(&report, NormType::L1),
+            1.0
+        );
+
+        // Trigger data is reduced modulo the cardinality.
+        let report =
+            request(11, 1.0).compute_report(&relevant_events_per_epoch);
+        assert_eq!(report.trigger_data, Some(3));
+
+        let report =
+            request(3, 1.0).compute_report(&RelevantEventsPerEpoch::new());
+        assert_eq!(report, AraEventLevelReport::default());
+    }
+
+    #[test]
+    fn test_perturbation() {
+        // With a huge epsilon, reports are (almost surely) kept as they are.
+        let request = request(3, 100.0);
+        let report = AraEventLevelReport {
+            trigger_data: Some(3),
+        };
+        assert_eq!(request.perturb_report(report.clone()), report);
+        let null_report = AraEventLevelReport::default();
+        assert_eq!(request.perturb_report(null_report.clone()), null_report);
+
+        // With epsilon = 0, null reports are turned into fake attributions
+        // most of the time (8 times out of 9), with all trigger data values.
+        let request = AraEventLevelRequest {
+            epsilon: 0.0,
+            ..request
+        };
+        let mut counts = [0; 9];
+        for _ in 0..9000 {
+            let report = request.perturb_report(null_report.clone());
+            counts[report.trigger_data.unwrap_or(8) as usize] += 1;
+        }
+        assert!(counts.iter().all(|count| *count > 800 && *count < 1200));
+    }
+}
@@ -1,18 +1,101 @@
 //! [Experimental] ARA-style requests, that mirror https://github.com/WICG/attribution-reporting-api/blob/main/AGGREGATE.md
 
-use std::{collections::HashMap, vec};
+use std::{cmp::Reverse, collections::HashMap};
 
 use crate::{
     events::{
         ara_event::AraEvent, hashmap_event_storage::VecEpochEvents,
         traits::RelevantEventSelector,
     },
-    queries::histogram::HistogramRequest,
+    mechanisms::NoiseScale,
+    queries::{
+        histogram::HistogramRequest,
+        traits::{RelevantEventsPerEpoch, RequestedPrivacyParameters},
+    },
 };
 
-#[derive(Debug, Clone)]
+/// Returns the relevant sources in the order in which ARA attributes them:
+/// highest priority first, then most recent first.
+pub(crate) fn get_sources_by_priority(
+    relevant_events_per_epoch: &RelevantEventsPerEpoch<
+        usize,
+        VecEpochEvents<AraEvent>,
+    >,
+) -> Vec<&AraEvent> {
+    let mut sources: Vec<&AraEvent> =
+        relevant_events_per_epoch.values().flatten().collect();
+    sources.sort_by_key(|source| {
+        (Reverse(source.priority), Reverse(source.source_time))
+    });
+    sources
+}
+
+/// A single ARA filter, i.e. one element of the `filters` or `not_filters`
+/// list of a trigger.
+#[derive(Debug, Clone, Default)]
+pub struct AraFilter {
+    /// Values to match against the source's filter data, by key.
+    pub values: HashMap<String, Vec<String>>,
+
+    /// Value of the reserved `_lookback_window` key, in seconds.
+    pub lookback_window: Option<u64>,
+}
+
+impl AraFilter {
+    /// Follows "match an attribution source's filter data against a filter"
+    /// from the ARA spec (https://wicg.github.io/attribution-reporting-api/#match-an-attribution-sources-filter-data-against-a-filter).
+    /// Keys that are missing from the source's filter data are ignored.
+    fn matches(
+        &self,
+        event: &AraEvent,
+        trigger_time: u64,
+        negated: bool,
+    ) -> bool {
+        if let Some(lookback_window) = self.lookback_window {
+            let in_window = trigger_time.saturating_sub(event.source_time)
+                <= lookback_window;
+            if in_window == negated {
+                return false;
+            }
+        }
+
+        self.values.iter().all(|(key, filter_values)| {
+            let source_type = [event.source_type.as_str().to_string()];
+            let source_values = if key == "source_type" {
+                &source_type[..]
+            } else {
+                match event.filter_data.get(key) {
+                    Some(source_values) => &source_values[..],
+                    None => return true,
+                }
+            };
+
+            if filter_values.is_empty() {
+                // An empty list only matches an empty list.
+                source_values.is_empty() != negated
+            } else {
+                let intersects = filter_values
+                    .iter()
+                    .any(|value| source_values.contains(value));
+                intersects != negated
+            }
+        })
+    }
+}
+
+/// Selects events that match the filters of an ARA trigger.
+#[derive(Debug, Clone, Default)]
 pub struct AraRelevantEventSelector {
-    pub filters: HashMap<String, Vec<String>>,
+    /// An event is relevant only if it matches at least one of these
+    /// filters, or if the list is empty.
+    pub filters: Vec<AraFilter>,
+
+    /// An event is relevant only if at least one of these filters doesn't
+    /// match it, or if the list is empty.
+    pub not_filters: Vec<AraFilter>,
+
+    /// Trigger registration time, in seconds since the Unix epoch.
+    pub trigger_time: u64,
     // TODO(https://github.com/columbia/pdslib/issues/8): add this if we drop events without the right source key
     // source_key: String,
 }
@@ -22,16 +105,22 @@ pub struct AraRelevantEventSelector {
 impl RelevantEventSelector for AraRelevantEventSelector {
     type Event = AraEvent;
 
-    fn is_relevant_event(&self, _event: &AraEvent) -> bool {
-        // TODO(https://github.com/columbia/pdslib/issues/8): add filters to events too, and implement ARA filtering
-        true
+    fn is_relevant_event(&self, event: &AraEvent) -> bool {
+        let matches_any = |filters: &[AraFilter], negated| {
+            filters.is_empty()
+                || filters.iter().any(|filter| {
+                    filter.matches(event, self.trigger_time, negated)
+                })
+        };
+        matches_any(&self.filters, false)
+            && matches_any(&self.not_filters, true)
     }
 }
 
 /// An instantiation of HistogramRequest that mimics ARA's types.
 /// The request corresponds to a trigger event in ARA.
-/// For now, each event is mapped to a single bucket, unlike ARA which supports
-/// packed queries (which can be emulated by running multiple queries).
+/// Each event is mapped to a single bucket, see `AraPackedHistogramRequest`
+/// for ARA's packed queries with multiple contributions per trigger.
 ///
 /// TODO(https://github.com/columbia/pdslib/issues/8): what is "nonMatchingKeyIdsIgnored"?
 #[derive(Debug)]
@@ -43,9 +132,12 @@ pub struct AraHistogramRequest {
     pub attributable_value: f64, /* E.g. 2^16 in ARA, with scaling as
                                   * post-processing */
     pub noise_scale: f64,
+    pub privacy_parameters: Option<RequestedPrivacyParameters>,
+    pub querier_id: String, // E.g. the reporting origin.
     pub source_key: String,
-    pub trigger_keypiece: usize,
+    pub trigger_keypiece: u128, // Use `parse_key_piece` for hex strings.
     pub filters: AraRelevantEventSelector,
+    pub deduplication_key: Option<u64>,
 }
 
 /// See https://github.com/WICG/attribution-reporting-api/blob/main/AGGREGATE.md#attribution-trigger-registration.
@@ -53,15 +145,26 @@ impl HistogramRequest for AraHistogramRequest {
     type EpochId = usize;
     type EpochEvents = VecEpochEvents<AraEvent>;
     type Event = AraEvent;
-    type BucketKey = usize;
+    type BucketKey = u128;
+    type QuerierId = String;
     type RelevantEventSelector = AraRelevantEventSelector;
 
     fn get_epochs_ids(&self) -> Vec<Self::EpochId> {
         (self.start_epoch..=self.end_epoch).rev().collect()
     }
 
-    fn get_laplace_noise_scale(&self) -> f64 {
-        self.noise_scale
+    fn get_querier_id(&self) -> String {
+        self.querier_id.clone()
+    }
+
+    fn get_noise_scale(&self) -> NoiseScale {
+        NoiseScale::Laplace(self.noise_scale)
+    }
+
+    fn get_requested_privacy_parameters(
+        &self,
+    ) -> Option<RequestedPrivacyParameters> {
+        self.privacy_parameters
     }
 
     fn get_attributable_value(&self) -> f64 {
@@ -72,6 +175,10 @@ impl HistogramRequest for AraHistogramRequest {
         self.filters.clone()
     }
 
+    fn get_deduplication_key(&self) -> Option<u64> {
+        self.deduplication_key
+    }
+
     fn get_bucket_key(&self, event: &AraEvent) -> Self::BucketKey {
         // TODO(https://github.com/columbia/pdslib/issues/8):
         // What does ARA do when the source key is not present?
@@ -85,26 +192,238 @@ impl HistogramRequest for AraHistogramRequest {
         source_keypiece | self.trigger_keypiece
     }
 
-    /// Returns the same value for each relevant event. Will be capped by
-    /// `compute_report`. An alternative would be to pick one event, or
-    /// split the attribution cap uniformly.
+    /// Returns the same value for each relevant event, by decreasing priority
+    /// and then most recent first. Will be capped by `compute_report`, so
+    /// low-priority sources are dropped first. An alternative would be to
+    /// pick one event, or split the attribution cap uniformly.
     ///
     /// TODO(https://github.com/columbia/pdslib/issues/8): Double check with
     /// Chromium logic.
     fn get_values<'a>(
         &self,
-        relevant_events_per_epoch: &'a HashMap<
+        relevant_events_per_epoch: &'a RelevantEventsPerEpoch<
             Self::EpochId,
             Self::EpochEvents,
         >,
     ) -> Vec<(&'a Self::Event, f64)> {
-        let mut event_values = vec![];
+        get_sources_by_priority(relevant_events_per_epoch)
+            .into_iter()
+            .map(|event| (event, self.per_event_attributable_value))
+            .collect()
+    }
+}
 
-        for relevant_events in relevant_events_per_epoch.values() {
-            for event in relevant_events.iter() {
-                event_values.push((event, self.per_event_attributable_value));
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        events::{
+            ara_event::{parse_key_piece, SourceType},
+            epoch_clock::EpochClock,
+        },
+        queries::traits::EpochReportRequest,
+    };
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    fn filter(values: &[(&str, &[&str])]) -> AraFilter {
+        AraFilter {
+            values: values
+                .iter()
+                .map(|(key, values)| (key.to_string(), strings(values)))
+                .collect(),
+            lookback_window: None,
+        }
+    }
+
+    /// Source from the filter example in the ARA explainer.
+    fn source() -> AraEvent {
+        let clock = EpochClock::new(1_000).unwrap();
+        AraEvent {
+            filter_data: HashMap::from([
+                (
+                    "conversion_subdomain".to_string(),
+                    strings(&[
+                        "electronics.megastore",
+                        "electronics2.megastore",
+                    ]),
+                ),
+                ("product".to_string(), strings(&["1234", "234"])),
+                ("ctid".to_string(), strings(&[])),
+            ]),
+            ..AraEvent::new(
+                1,
+                HashMap::new(),
+                SourceType::Navigation,
+                1_000,
+                &clock,
+            )
+        }
+    }
+
+    fn selector(
+        filters: Vec<AraFilter>,
+        not_filters: Vec<AraFilter>,
+    ) -> AraRelevantEventSelector {
+        AraRelevantEventSelector {
+            filters,
+            not_filters,
+            trigger_time: 2_000,
         }
-        event_values
+    }
+
+    #[test]
+    fn test_ara_filters() {
+        let event = source();
+        let is_relevant = |filters, not_filters| {
+            selector(filters, not_filters).is_relevant_event(&event)
+        };
+
+        // No filters match everything.
+        assert!(is_relevant(vec![], vec![]));
+
+        // Example from the explainer: `directory` is not in the source's
+        // filter data, so it is ignored.
+        assert!(is_relevant(
+            vec![filter(&[
+                ("conversion_subdomain", &["electronics.megastore"]),
+                ("directory", &["/store/electronics"]),
+            ])],
+            vec![]
+        ));
+
+        // All keys of a filter need to match.
+        assert!(!is_relevant(
+            vec![filter(&[
+                ("conversion_subdomain", &["electronics.megastore"]),
+                ("product", &["5678"]),
+            ])],
+            vec![]
+        ));
+
+        // One filter of the list is enough.
+        assert!(is_relevant(
+            vec![
+                filter(&[("product", &["5678"])]),
+                filter(&[("product", &["234"])])
+            ],
+            vec![]
+        ));
+
+        // Example from the explainer: the source's product is in the
+        // not_filters.
+        assert!(!is_relevant(
+            vec![],
+            vec![filter(&[("product", &["1234"])])]
+        ));
+        assert!(is_relevant(vec![], vec![filter(&[("product", &["5678"])])]));
+
+        // Empty lists only match empty lists.
+        assert!(is_relevant(vec![filter(&[("ctid", &[])])], vec![]));
+        assert!(!is_relevant(vec![filter(&[("product", &[])])], vec![]));
+        assert!(is_relevant(vec![], vec![filter(&[("product", &[])])]));
+        assert!(!is_relevant(vec![], vec![filter(&[("ctid", &[])])]));
+    }
+
+    #[test]
+    fn test_ara_128_bit_bucket_keys() {
+        let mut event = source();
+        event.aggregatable_sources = HashMap::from([(
+            "campaignCounts".to_string(),
+            parse_key_piece("0x80000000000000000000000000000159").unwrap(),
+        )]);
+        let request = AraHistogramRequest {
+            start_epoch: 1,
+            end_epoch: 1,
+            per_event_attributable_value: 1.0,
+            attributable_value: 1.0,
+            noise_scale: 1.0,
+            privacy_parameters: None,
+            querier_id: "https://ad-tech.example".to_string(),
+            source_key: "campaignCounts".to_string(),
+            trigger_keypiece: parse_key_piece("0x400").unwrap(),
+            filters: AraRelevantEventSelector::default(),
+            deduplication_key: None,
+        };
+
+        // The high bit of the source key piece is kept.
+        assert_eq!(
+            request.get_bucket_key(&event),
+            0x80000000000000000000000000000559
+        );
+    }
+
+    #[test]
+    fn test_ara_priority() {
+        let source = |id, epoch_number, priority, source_time| AraEvent {
+            id,
+            epoch_number,
+            priority,
+            source_time,
+            aggregatable_sources: HashMap::from([(
+                "campaignCounts".to_string(),
+                id as u128,
+            )]),
+            ..source()
+        };
+        let relevant_events_per_epoch = RelevantEventsPerEpoch::from_iter([
+            (2, vec![source(3, 2, 0, 200)]),
+            (1, vec![source(1, 1, 5, 100), source(2, 1, 0, 150)]),
+        ]);
+
+        // The old high-priority source goes first, then the others by recency.
+        let ids: Vec<usize> =
+            get_sources_by_priority(&relevant_events_per_epoch)
+                .iter()
+                .map(|source| source.id)
+                .collect();
+        assert_eq!(ids, vec![1, 3, 2]);
+
+        // With a cap of one source, the high-priority source is attributed.
+        let request = AraHistogramRequest {
+            start_epoch: 1,
+            end_epoch: 2,
+            per_event_attributable_value: 1.0,
+            attributable_value: 1.0,
+            noise_scale: 1.0,
+            privacy_parameters: None,
+            querier_id: "https://ad-tech.example".to_string(),
+            source_key: "campaignCounts".to_string(),
+            trigger_keypiece: 0x400,
+            filters: AraRelevantEventSelector::default(),
+            deduplication_key: None,
+        };
+        let values = request.get_values(&relevant_events_per_epoch);
+        assert_eq!(values.len(), 3);
+        let report = request.compute_report(&relevant_events_per_epoch);
+        assert_eq!(report.bin_values, HashMap::from([(0x401, 1.0)]));
+    }
+
+    #[test]
+    fn test_ara_reserved_filter_keys() {
+        let mut event = source();
+        let navigation_only =
+            selector(vec![filter(&[("source_type", &["navigation"])])], vec![]);
+        assert!(navigation_only.is_relevant_event(&event));
+        event.source_type = SourceType::Event;
+        assert!(!navigation_only.is_relevant_event(&event));
+
+        // The trigger is 1000 seconds after the source.
+        let lookback = |lookback_window| AraFilter {
+            values: HashMap::new(),
+            lookback_window: Some(lookback_window),
+        };
+        assert!(
+            selector(vec![lookback(1_000)], vec![]).is_relevant_event(&event)
+        );
+        assert!(
+            !selector(vec![lookback(999)], vec![]).is_relevant_event(&event)
+        );
+        assert!(
+            !selector(vec![], vec![lookback(1_000)]).is_relevant_event(&event)
+        );
+        assert!(selector(vec![], vec![lookback(999)]).is_relevant_event(&event));
     }
 }
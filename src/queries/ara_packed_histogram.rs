@@ -0,0 +1,275 @@
+//! [Experimental] ARA-style requests with packed trigger data, that produce
+//! multiple contributions per trigger. Mirrors https://github.com/WICG/attribution-reporting-api/blob/main/AGGREGATE.md#attribution-trigger-registration
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::bail;
+
+use crate::{
+    events::{ara_event::AraEvent, hashmap_event_storage::VecEpochEvents},
+    mechanisms::{NoiseScale, NormType},
+    queries::{
+        ara_histogram::{get_sources_by_priority, AraRelevantEventSelector},
+        histogram::{get_histogram_distance, HistogramReport},
+        traits::{EpochReportRequest, RelevantEventsPerEpoch, ReportRequest},
+    },
+};
+
+/// One entry of ARA's `aggregatable_trigger_data`: a key piece that is ORed
+/// into the source key pieces of each key id in `source_keys`.
+#[derive(Debug, Clone)]
+pub struct AraTriggerData {
+    pub key_piece: u128, // Use `parse_key_piece` for hex strings.
+    pub source_keys: Vec<String>,
+}
+
+/// ARA trigger with packed `aggregatable_trigger_data` and
+/// `aggregatable_values`. Unlike `AraHistogramRequest`, which maps each event
+/// to a single bucket, the attributed source contributes to one bucket per
+/// key id in `aggregatable_values`, so a single request (and a single
+/// privacy accounting pass) gives all the contributions of the trigger.
+///
+/// As in ARA, the trigger is attributed to a single source, the relevant
+/// source with the highest priority, and the most recent one among ties.
+#[derive(Debug)]
+pub struct AraPackedHistogramRequest {
+    pub start_epoch: usize,
+    pub end_epoch: usize,
+    pub attributable_value: f64, /* E.g. 2^16 in ARA, with scaling as
+                                  * post-processing */
+    pub noise_scale: f64,
+    pub querier_id: String, // E.g. the reporting origin.
+    pub aggregatable_trigger_data: Vec<AraTriggerData>,
+    pub aggregatable_values: HashMap<String, f64>,
+    pub filters: AraRelevantEventSelector,
+    pub deduplication_key: Option<u64>,
+}
+
+impl AraPackedHistogramRequest {
+    /// Returns the contributions of `source`, as (bucket key, value) pairs
+    /// sorted by key id.
+    fn get_contributions(&self, source: &AraEvent) -> Vec<(u128, f64)> {
+        // Sort by key id so the contribution cap is applied deterministically.
+        let values: BTreeMap<&String, f64> = self
+            .aggregatable_values
+            .iter()
+            .map(|(key_id, value)| (key_id, *value))
+            .collect();
+
+        let mut contributions = vec![];
+        for (key_id, value) in values {
+            // Key ids that the source doesn't have are dropped, like in ARA.
+            let Some(source_key_piece) =
+                source.aggregatable_sources.get(key_id)
+            else {
+                continue;
+            };
+            let bucket_key = self
+                .aggregatable_trigger_data
+                .iter()
+                .filter(|trigger_data| {
+                    trigger_data.source_keys.contains(key_id)
+                })
+                .fold(*source_key_piece, |bucket_key, trigger_data| {
+                    bucket_key | trigger_data.key_piece
+                });
+            contributions.push((bucket_key, value));
+        }
+        contributions
+    }
+}
+
+impl ReportRequest for AraPackedHistogramRequest {
+    type Report = HistogramReport<u128>;
+}
+
+impl EpochReportRequest for AraPackedHistogramRequest {
+    type EpochId = usize;
+    type EpochEvents = VecEpochEvents<AraEvent>;
+    type QuerierId = String;
+    type ReportGlobalSensitivity = f64;
+    type RelevantEventSelector = AraRelevantEventSelector;
+
+    fn get_epoch_ids(&self) -> Vec<Self::EpochId> {
+        (self.start_epoch..=self.end_epoch).rev().collect()
+    }
+
+    fn get_querier_id(&self) -> String {
+        self.querier_id.clone()
+    }
+
+    fn get_relevant_event_selector(&self) -> Self::RelevantEventSelector {
+        self.filters.clone()
+    }
+
+    /// Attributes the trigger to a single source, and adds its contributions
+    /// in key id order until the cap is reached.
+    fn compute_report(
+        &self,
+        relevant_events_per_epoch: &RelevantEventsPerEpoch<
+            usize,
+            Self::EpochEvents,
+        >,
+    ) -> Self::Report {
+        let sources = get_sources_by_priority(relevant_events_per_epoch);
+        let Some(source) = sources.first() else {
+            return HistogramReport::default();
+        };
+
+        let mut bin_values: HashMap<u128, f64> = HashMap::new();
+        let mut total_value: f64 = 0.0;
+        for (bucket_key, value) in self.get_contributions(source) {
+            total_value += value;
+            if total_value > self.attributable_value {
+                // Return partial attribution to stay within the cap.
+                break;
+            }
+            *bin_values.entry(bucket_key).or_default() += value;
+        }
+        HistogramReport { bin_values }
+    }
+
+    fn get_single_epoch_individual_sensitivity(
+        &self,
+        report: &Self::Report,
+        norm_type: NormType,
+    ) -> f64 {
+        match norm_type {
+            NormType::L1 => report.bin_values.values().map(|x| x.abs()).sum(),
+            NormType::L2 => {
+                let sum_squares: f64 =
+                    report.bin_values.values().map(|x| x * x).sum();
+                sum_squares.sqrt()
+            }
+        }
+    }
+
+    /// Same as for `HistogramRequest`, since contributions can go to
+    /// multiple bins (https://arxiv.org/pdf/2405.16719, Thm. 18).
+    fn get_report_global_sensitivity(&self) -> f64 {
+        2.0 * self.attributable_value
+    }
+
+    fn get_report_distance(
+        &self,
+        report: &Self::Report,
+        other_report: &Self::Report,
+        norm_type: NormType,
+    ) -> Option<f64> {
+        Some(get_histogram_distance(report, other_report, norm_type))
+    }
+
+    fn get_noise_scale(&self) -> NoiseScale {
+        NoiseScale::Laplace(self.noise_scale)
+    }
+
+    fn get_deduplication_key(&self) -> Option<u64> {
+        self.deduplication_key
+    }
+
+    /// ARA only allows aggregatable values in [1, 65536]. We only require
+    /// positive finite values, since other values would bypass the
+    /// contribution cap and the global sensitivity.
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        for (key_id, value) in &self.aggregatable_values {
+            if !(value.is_finite() && *value > 0.0) {
+                bail!("Invalid aggregatable value {} for {}", value, key_id);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{
+        ara_event::{parse_key_piece, SourceType},
+        epoch_clock::EpochClock,
+    };
+
+    fn source(id: usize, epoch_number: usize) -> AraEvent {
+        let aggregatable_sources = HashMap::from([
+            (
+                "campaignCounts".to_string(),
+                parse_key_piece("0x159").unwrap(),
+            ),
+            ("geoValue".to_string(), parse_key_piece("0x5").unwrap()),
+        ]);
+        let clock = EpochClock::new(100).unwrap();
+        AraEvent::new(
+            id,
+            aggregatable_sources,
+            SourceType::Navigation,
+            clock.get_epoch_start(epoch_number),
+            &clock,
+        )
+    }
+
+    /// Trigger from the example in the ARA explainer.
+    fn request(attributable_value: f64) -> AraPackedHistogramRequest {
+        AraPackedHistogramRequest {
+            start_epoch: 1,
+            end_epoch: 2,
+            attributable_value,
+            noise_scale: 1.0,
+            querier_id: "https://ad-tech.example".to_string(),
+            aggregatable_trigger_data: vec![
+                AraTriggerData {
+                    key_piece: parse_key_piece("0x400").unwrap(),
+                    source_keys: vec!["campaignCounts".to_string()],
+                },
+                AraTriggerData {
+                    key_piece: parse_key_piece("0xA80").unwrap(),
+                    source_keys: vec![
+                        "geoValue".to_string(),
+                        "nonMatch".to_string(),
+                    ],
+                },
+            ],
+            aggregatable_values: HashMap::from([
+                ("campaignCounts".to_string(), 32768.0),
+                ("geoValue".to_string(), 1664.0),
+                ("nonMatch".to_string(), 100.0),
+            ]),
+            filters: AraRelevantEventSelector::default(),
+            deduplication_key: None,
+        }
+    }
+
+    #[test]
+    fn test_packed_contributions() {
+        let relevant_events_per_epoch = RelevantEventsPerEpoch::from_iter([
+            (2, vec![source(2, 2), source(3, 2)]),
+            (1, vec![source(1, 1)]),
+        ]);
+
+        let report =
+            request(65536.0).compute_report(&relevant_events_per_epoch);
+        assert_eq!(
+            report.bin_values,
+            HashMap::from([(0x559, 32768.0), (0xa85, 1664.0)])
+        );
+
+        // Contributions are capped in key id order.
+        let report =
+            request(32768.0).compute_report(&relevant_events_per_epoch);
+        assert_eq!(report.bin_values, HashMap::from([(0x559, 32768.0)]));
+
+        let report =
+            request(65536.0).compute_report(&RelevantEventsPerEpoch::new());
+        assert!(report.bin_values.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_aggregatable_values() {
+        assert!(request(65536.0).validate().is_ok());
+        for value in [f64::NAN, -1.0, 0.0, f64::INFINITY] {
+            let mut request = request(65536.0);
+            request
+                .aggregatable_values
+                .insert("geoValue".to_string(), value);
+            assert!(request.validate().is_err());
+        }
+    }
+}
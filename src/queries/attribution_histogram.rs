@@ -0,0 +1,457 @@
+//! [Experimental] Histogram requests for common multi-touch attribution
+//! models, that work with any event type.
+
+use std::{collections::HashSet, fmt::Debug};
+
+use anyhow::bail;
+
+use crate::{
+    events::{
+        epoch_clock::EpochClock,
+        hashmap_event_storage::VecEpochEvents,
+        traits::{Event, RelevantEventSelector},
+    },
+    mechanisms::NoiseScale,
+    queries::{
+        histogram::{BucketKey, HistogramRequest},
+        lookback_window::{LookbackWindow, LookbackWindowSelector},
+        traits::{QuerierId, RelevantEventsPerEpoch},
+    },
+};
+
+/// How the attributable value is split across the relevant events, from the
+/// oldest event to the most recent one.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributionModel {
+    /// All the value goes to the most recent event.
+    LastTouch,
+
+    /// All the value goes to the oldest event.
+    FirstTouch,
+
+    /// The value is split uniformly across events.
+    Linear,
+
+    /// Each event gets a weight that halves every `half_life_epochs` epochs
+    /// between its epoch and the most recent requested epoch.
+    TimeDecay { half_life_epochs: f64 },
+
+    /// U-shaped model: the oldest and most recent events get
+    /// `first_fraction` and `last_fraction` of the value, and the rest is
+    /// split uniformly across the events in between. With two events, the
+    /// value is split proportionally to the two fractions. Typically 40%,
+    /// 40% and 20% for the middle events.
+    PositionBased {
+        first_fraction: f64,
+        last_fraction: f64,
+    },
+}
+
+impl AttributionModel {
+    /// Checks that the parameters of the model give well-defined weights.
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        match self {
+            AttributionModel::TimeDecay { half_life_epochs }
+                if half_life_epochs.is_nan() || *half_life_epochs <= 0.0 =>
+            {
+                bail!("Half-life must be positive, got {}", half_life_epochs);
+            }
+            AttributionModel::PositionBased {
+                first_fraction,
+                last_fraction,
+            } => {
+                for fraction in [first_fraction, last_fraction] {
+                    if !(0.0..=1.0).contains(fraction) {
+                        bail!("Fractions must be in [0, 1], got {}", fraction);
+                    }
+                }
+                if first_fraction + last_fraction <= 0.0 {
+                    bail!("At least one fraction must be positive");
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Returns the weight of each event, given the age (in epochs) of each
+    /// event from the oldest to the most recent. Weights sum to 1.
+    fn get_weights(&self, ages: &[usize]) -> Vec<f64> {
+        let n = ages.len();
+        if n == 0 {
+            return vec![];
+        }
+
+        let mut weights = vec![0.0; n];
+        match self {
+            AttributionModel::LastTouch => weights[n - 1] = 1.0,
+            AttributionModel::FirstTouch => weights[0] = 1.0,
+            AttributionModel::Linear => weights.fill(1.0 / n as f64),
+            AttributionModel::TimeDecay { half_life_epochs } => {
+                for (weight, age) in weights.iter_mut().zip(ages) {
+                    *weight = (-(*age as f64) / half_life_epochs).exp2();
+                }
+                let total: f64 = weights.iter().sum();
+                weights.iter_mut().for_each(|weight| *weight /= total);
+            }
+            AttributionModel::PositionBased {
+                first_fraction,
+                last_fraction,
+            } => match n {
+                1 => weights[0] = 1.0,
+                2 => {
+                    let total = first_fraction + last_fraction;
+                    weights[0] = first_fraction / total;
+                    weights[1] = last_fraction / total;
+                }
+                _ => {
+                    let middle_fraction =
+                        (1.0 - first_fraction - last_fraction).max(0.0);
+                    weights.fill(middle_fraction / (n - 2) as f64);
+                    weights[0] = *first_fraction;
+                    weights[n - 1] = *last_fraction;
+                }
+            },
+        }
+        weights
+    }
+}
+
+/// Histogram request that attributes the relevant events with an
+/// `AttributionModel`. Generic over the event type, `bucket_key` maps each
+/// event to its bin. With a `lookback_window`, only the events in the window
+/// are relevant: set it with `with_lookback_window`, which requests the
+/// epochs that overlap with the window. Declaring `possible_bucket_keys`
+/// drops other buckets, and saves budget across epochs with a single bucket.
+#[derive(Debug, Clone)]
+pub struct AttributionHistogramRequest<
+    E: Event,
+    QI: QuerierId,
+    RES: RelevantEventSelector<Event = E>,
+    BK: BucketKey,
+> {
+    /// Requested epochs, most recent first.
+    pub epoch_ids: Vec<E::EpochId>,
+    pub attributable_value: f64,
+    pub noise_scale: NoiseScale,
+    pub querier_id: QI,
+    pub model: AttributionModel,
+    pub relevant_event_selector: RES,
+    pub lookback_window: Option<LookbackWindow>,
+    pub bucket_key: fn(&E) -> BK,
+    pub possible_bucket_keys: Option<HashSet<BK>>,
+}
+
+impl<E, QI, RES, BK> AttributionHistogramRequest<E, QI, RES, BK>
+where
+    E: Event,
+    E::EpochId: From<usize>,
+    QI: QuerierId,
+    RES: RelevantEventSelector<Event = E>,
+    BK: BucketKey,
+{
+    /// Restricts the request to the events in `lookback_window`, and
+    /// requests the epochs of `epoch_clock` that overlap with the window
+    /// instead of `epoch_ids`.
+    pub fn with_lookback_window(
+        self,
+        lookback_window: LookbackWindow,
+        epoch_clock: &EpochClock,
+    ) -> Self {
+        Self {
+            epoch_ids: lookback_window
+                .get_epoch_ids(epoch_clock)
+                .into_iter()
+                .map(E::EpochId::from)
+                .collect(),
+            lookback_window: Some(lookback_window),
+            ..self
+        }
+    }
+}
+
+impl<E, QI, RES, BK> HistogramRequest
+    for AttributionHistogramRequest<E, QI, RES, BK>
+where
+    E: Event + Debug,
+    QI: QuerierId,
+    RES: RelevantEventSelector<Event = E> + Clone + Debug,
+    BK: BucketKey,
+{
+    type EpochId = E::EpochId;
+    type EpochEvents = VecEpochEvents<E>;
+    type Event = E;
+    type BucketKey = BK;
+    type QuerierId = QI;
+    type RelevantEventSelector = LookbackWindowSelector<RES>;
+
+    fn get_epochs_ids(&self) -> Vec<E::EpochId> {
+        self.epoch_ids.clone()
+    }
+
+    fn get_querier_id(&self) -> QI {
+        self.querier_id.clone()
+    }
+
+    fn get_noise_scale(&self) -> NoiseScale {
+        self.noise_scale
+    }
+
+    fn get_attributable_value(&self) -> f64 {
+        self.attributable_value
+    }
+
+    fn get_relevant_event_selector(&self) -> LookbackWindowSelector<RES> {
+        LookbackWindowSelector {
+            selector: self.relevant_event_selector.clone(),
+            lookback_window: self.lookback_window,
+        }
+    }
+
+    fn get_bucket_key(&self, event: &E) -> BK {
+        (self.bucket_key)(event)
+    }
+
+    fn get_possible_bucket_keys(&self) -> Option<&HashSet<BK>> {
+        self.possible_bucket_keys.as_ref()
+    }
+
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        self.model.validate()
+    }
+
+    /// Returns events from the oldest to the most recent, so the cap can't
+    /// drop the events that the model favors. Values sum to at most the
+    /// attributable value, even with floating point errors. Events that get
+    /// no value are skipped, so they don't create empty bins.
+    fn get_values<'a>(
+        &self,
+        relevant_events_per_epoch: &'a RelevantEventsPerEpoch<
+            E::EpochId,
+            VecEpochEvents<E>,
+        >,
+    ) -> Vec<(&'a E, f64)> {
+        let mut events = vec![];
+        let mut ages = vec![];
+        for (age, epoch_id) in self.epoch_ids.iter().enumerate().rev() {
+            if let Some(epoch_events) = relevant_events_per_epoch.get(epoch_id)
+            {
+                for event in epoch_events {
+                    events.push(event);
+                    ages.push(age);
+                }
+            }
+        }
+
+        let weights = self.model.get_weights(&ages);
+        let mut total_value = 0.0;
+        events
+            .into_iter()
+            .zip(weights)
+            .map(|(event, weight)| {
+                let mut value = (weight * self.attributable_value)
+                    .min(self.attributable_value - total_value)
+                    .max(0.0);
+                // Round down so the running sum never goes over the cap.
+                while value > 0.0
+                    && total_value + value > self.attributable_value
+                {
+                    value = value.next_down().max(0.0);
+                }
+                total_value += value;
+                (event, value)
+            })
+            .filter(|(_, value)| *value > 0.0)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        events::{epoch_clock::EpochClock, simple_event::SimpleEvent},
+        queries::{
+            simple_last_touch_histogram::SimpleRelevantEventSelector,
+            traits::EpochReportRequest,
+        },
+    };
+
+    type Request = AttributionHistogramRequest<
+        SimpleEvent,
+        usize,
+        SimpleRelevantEventSelector,
+        usize,
+    >;
+
+    fn request(model: AttributionModel, attributable_value: f64) -> Request {
+        AttributionHistogramRequest {
+            epoch_ids: vec![3, 2, 1],
+            attributable_value,
+            noise_scale: NoiseScale::Laplace(1.0),
+            querier_id: 1,
+            model,
+            relevant_event_selector: SimpleRelevantEventSelector {
+                lambda: |_| true,
+            },
+            lookback_window: None,
+            bucket_key: |event| event.event_key,
+            possible_bucket_keys: None,
+        }
+    }
+
+    /// Four events: one in epoch 1, two in epoch 2 and one in epoch 3.
+    fn events() -> RelevantEventsPerEpoch<usize, Vec<SimpleEvent>> {
+        let clock = EpochClock::new(100).unwrap();
+        let event = |id, epoch_number| {
+            SimpleEvent::new(
+                id,
+                clock.get_epoch_start(epoch_number),
+                id,
+                &clock,
+            )
+        };
+        RelevantEventsPerEpoch::from_iter([
+            (3, vec![event(4, 3)]),
+            (2, vec![event(2, 2), event(3, 2)]),
+            (1, vec![event(1, 1)]),
+        ])
+    }
+
+    fn values(request: &Request) -> Vec<(usize, f64)> {
+        let events = events();
+        request
+            .get_values(&events)
+            .into_iter()
+            .map(|(event, value)| (event.id, value))
+            .collect()
+    }
+
+    #[test]
+    fn test_attribution_models() {
+        let request_values = |model| values(&request(model, 100.0));
+
+        assert_eq!(
+            request_values(AttributionModel::LastTouch),
+            vec![(4, 100.0)]
+        );
+        assert_eq!(
+            request_values(AttributionModel::FirstTouch),
+            vec![(1, 100.0)]
+        );
+        assert_eq!(
+            request_values(AttributionModel::Linear),
+            vec![(1, 25.0), (2, 25.0), (3, 25.0), (4, 25.0)]
+        );
+        let position_based = request_values(AttributionModel::PositionBased {
+            first_fraction: 0.4,
+            last_fraction: 0.4,
+        });
+        let expected = [40.0, 10.0, 10.0, 40.0];
+        for ((_, value), expected) in position_based.iter().zip(expected) {
+            assert!((value - expected).abs() < 1e-9);
+        }
+
+        // Weights 1/4, 1/2, 1/2 and 1, normalized by their sum 9/4.
+        let time_decay = request_values(AttributionModel::TimeDecay {
+            half_life_epochs: 1.0,
+        });
+        let expected = [100.0 / 9.0, 200.0 / 9.0, 200.0 / 9.0, 400.0 / 9.0];
+        for ((_, value), expected) in time_decay.iter().zip(expected) {
+            assert!((value - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_attribution_models_respect_cap() {
+        // Models and the number of events that they attribute a value to.
+        let models = [
+            (AttributionModel::LastTouch, 1),
+            (AttributionModel::FirstTouch, 1),
+            (AttributionModel::Linear, 4),
+            (
+                AttributionModel::TimeDecay {
+                    half_life_epochs: 0.7,
+                },
+                4,
+            ),
+            (
+                AttributionModel::PositionBased {
+                    first_fraction: 0.3,
+                    last_fraction: 0.3,
+                },
+                4,
+            ),
+        ];
+        for (model, num_bins) in models {
+            for attributable_value in [0.1, 1.0, 3.0, 7.0, 65536.0] {
+                let request = request(model.clone(), attributable_value);
+                let events = events();
+
+                // No event is dropped by the cap in `compute_report`, and
+                // events without value don't create empty bins.
+                let report = request.compute_report(&events);
+                assert_eq!(report.bin_values.len(), num_bins);
+                let total: f64 = report.bin_values.values().sum();
+                assert!(
+                    (total - attributable_value).abs()
+                        < 1e-9 * attributable_value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_position_based_with_few_events() {
+        let model = AttributionModel::PositionBased {
+            first_fraction: 0.4,
+            last_fraction: 0.2,
+        };
+        assert_eq!(model.get_weights(&[0]), vec![1.0]);
+        let weights = model.get_weights(&[1, 0]);
+        assert!((weights[0] - 2.0 / 3.0).abs() < 1e-12);
+        assert!((weights[1] - 1.0 / 3.0).abs() < 1e-12);
+        assert!(model.get_weights(&[]).is_empty());
+
+        // Fractions that sum to more than 1 are capped.
+        let model = AttributionModel::PositionBased {
+            first_fraction: 0.7,
+            last_fraction: 0.7,
+        };
+        let request = request(model, 1.0);
+        let values = values(&request);
+        assert_eq!(values[0], (1, 0.7));
+        assert_eq!(values[1].0, 4);
+        assert!((values[1].1 - 0.3).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_invalid_attribution_models() {
+        let invalid_models = [
+            AttributionModel::TimeDecay {
+                half_life_epochs: 0.0,
+            },
+            AttributionModel::TimeDecay {
+                half_life_epochs: f64::NAN,
+            },
+            AttributionModel::PositionBased {
+                first_fraction: 0.0,
+                last_fraction: 0.0,
+            },
+            AttributionModel::PositionBased {
+                first_fraction: -0.2,
+                last_fraction: 0.6,
+            },
+            AttributionModel::PositionBased {
+                first_fraction: 0.4,
+                last_fraction: f64::NAN,
+            },
+        ];
+        for model in invalid_models {
+            let request = request(model, 1.0);
+            assert!(EpochReportRequest::validate(&request).is_err());
+        }
+        let request = request(AttributionModel::LastTouch, 1.0);
+        assert!(EpochReportRequest::validate(&request).is_ok());
+    }
+}
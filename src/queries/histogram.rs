@@ -1,10 +1,18 @@
-use std::{collections::HashMap, fmt::Debug, hash::Hash};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    hash::Hash,
+};
+
+use rand::{CryptoRng, Rng};
 
 use crate::{
-    budget::pure_dp_filter::PureDPBudget,
     events::traits::{EpochEvents, EpochId, Event, RelevantEventSelector},
-    mechanisms::{NoiseScale, NormType},
-    queries::traits::{EpochReportRequest, Report, ReportRequest},
+    mechanisms::{samplers::sample_laplace, NoiseScale, NormType},
+    queries::traits::{
+        EpochReportRequest, QuerierId, RelevantEventsPerEpoch, Report,
+        ReportRequest, RequestedPrivacyParameters,
+    },
 };
 
 #[derive(Debug, Clone)]
@@ -18,6 +26,9 @@ pub trait BucketKey: Debug + Hash + Eq {}
 /// Default type for bucket keys.
 impl BucketKey for usize {}
 
+/// 128-bit bucket keys, e.g. for ARA aggregation keys.
+impl BucketKey for u128 {}
+
 /// Default histogram has no bins (null report).
 impl<BK> Default for HistogramReport<BK> {
     fn default() -> Self {
@@ -29,6 +40,61 @@ impl<BK> Default for HistogramReport<BK> {
 
 impl<BK: BucketKey> Report for HistogramReport<BK> {}
 
+/// Norm of the difference between two histograms.
+pub(crate) fn get_histogram_distance<BK: BucketKey>(
+    report: &HistogramReport<BK>,
+    other_report: &HistogramReport<BK>,
+    norm_type: NormType,
+) -> f64 {
+    let value = |report: &HistogramReport<BK>, bucket_key| {
+        report.bin_values.get(bucket_key).copied().unwrap_or(0.0)
+    };
+    let other_bucket_keys = other_report
+        .bin_values
+        .keys()
+        .filter(|bucket_key| !report.bin_values.contains_key(*bucket_key));
+    let differences =
+        report
+            .bin_values
+            .keys()
+            .chain(other_bucket_keys)
+            .map(|bucket_key| {
+                (value(report, bucket_key) - value(other_report, bucket_key))
+                    .abs()
+            });
+    match norm_type {
+        NormType::L1 => differences.sum(),
+        NormType::L2 => differences.map(|x| x * x).sum::<f64>().sqrt(),
+    }
+}
+
+/// Local stand-in for the aggregator, e.g. for simulations: sums `reports`
+/// and adds Laplace noise to every bin in `bucket_keys`. Empty bins get noise
+/// too, since dropping them would reveal which keys appear in the reports.
+/// Bins outside of `bucket_keys` are dropped.
+pub fn aggregate_with_laplace_noise<BK, R>(
+    reports: &[HistogramReport<BK>],
+    bucket_keys: &[BK],
+    laplace_noise_scale: f64,
+    rng: &mut R,
+) -> HashMap<BK, f64>
+where
+    BK: BucketKey + Clone,
+    R: Rng + CryptoRng + ?Sized,
+{
+    bucket_keys
+        .iter()
+        .map(|bucket_key| {
+            let sum: f64 = reports
+                .iter()
+                .filter_map(|report| report.bin_values.get(bucket_key))
+                .sum();
+            let noise = sample_laplace(laplace_noise_scale, rng);
+            (bucket_key.clone(), sum + noise)
+        })
+        .collect()
+}
+
 /// [Experimental] Trait for generic histogram requests. Any type satisfying
 /// this interface will be callable as a valid ReportRequest with the right
 /// accounting. Following the formalism from https://arxiv.org/pdf/2405.16719, Thm 18.
@@ -38,14 +104,28 @@ pub trait HistogramRequest: Debug {
     type EpochEvents: EpochEvents;
     type Event: Event;
     type BucketKey: BucketKey;
+    type QuerierId: QuerierId;
     type RelevantEventSelector: RelevantEventSelector<Event = Self::Event>;
 
     /// Returns the ids of the epochs that are relevant for this query.
     /// Typically a range of epochs.
     fn get_epochs_ids(&self) -> Vec<Self::EpochId>;
 
-    /// Returns the Laplace noise scale added after summing all the reports.
-    fn get_laplace_noise_scale(&self) -> f64;
+    /// Returns the identifier of the querier issuing this query.
+    fn get_querier_id(&self) -> Self::QuerierId;
+
+    /// Returns the noise added after summing all the reports, e.g. Laplace
+    /// noise, or Gaussian noise to charge multi-bucket histograms their L2
+    /// norm instead of their L1 norm.
+    fn get_noise_scale(&self) -> NoiseScale;
+
+    /// Returns the declared privacy parameters of the query, if any. See
+    /// `EpochReportRequest::get_requested_privacy_parameters`.
+    fn get_requested_privacy_parameters(
+        &self,
+    ) -> Option<RequestedPrivacyParameters> {
+        None
+    }
 
     /// Returns the maximum attributable value, i.e. the maximum L1 norm of an
     /// attributed histogram.
@@ -56,16 +136,37 @@ pub trait HistogramRequest: Debug {
     /// indicating whether a single event is relevant.
     fn get_relevant_event_selector(&self) -> Self::RelevantEventSelector;
 
+    /// Returns the deduplication key of the trigger, if any. See
+    /// `EpochReportRequest::get_deduplication_key`.
+    fn get_deduplication_key(&self) -> Option<u64> {
+        None
+    }
+
+    /// Checks that the request is well-formed. See
+    /// `EpochReportRequest::validate`.
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
     /// Returns the histogram bucket key (bin) for a given event.
     fn get_bucket_key(&self, event: &Self::Event) -> Self::BucketKey;
 
+    /// Returns the set of bucket keys that reports can use, if the request
+    /// declares one. Values attributed to other buckets are dropped, so
+    /// requests with a single possible bucket have a tighter global
+    /// sensitivity.
+    fn get_possible_bucket_keys(&self) -> Option<&HashSet<Self::BucketKey>> {
+        None
+    }
+
     /// Attributes a value to each event in `relevant_events_per_epoch`, which
     /// will be obtained by retrieving *relevant* events from the event
     /// storage. Events can point to the relevant_events_per_epoch, hence
-    /// the lifetime.
+    /// the lifetime. Values are capped in the returned order, so the order
+    /// must only depend on the order of `relevant_events_per_epoch`.
     fn get_values<'a>(
         &self,
-        relevant_events_per_epoch: &'a HashMap<
+        relevant_events_per_epoch: &'a RelevantEventsPerEpoch<
             Self::EpochId,
             Self::EpochEvents,
         >,
@@ -81,7 +182,7 @@ impl<H: HistogramRequest> ReportRequest for H {
 impl<H: HistogramRequest> EpochReportRequest for H {
     type EpochId = H::EpochId;
     type EpochEvents = H::EpochEvents;
-    type PrivacyBudget = PureDPBudget;
+    type QuerierId = H::QuerierId;
     type ReportGlobalSensitivity = f64;
     type RelevantEventSelector = H::RelevantEventSelector; // Use the full request as the selector.
 
@@ -92,19 +193,40 @@ impl<H: HistogramRequest> EpochReportRequest for H {
         self.get_epochs_ids()
     }
 
+    fn get_querier_id(&self) -> H::QuerierId {
+        self.get_querier_id()
+    }
+
     fn get_relevant_event_selector(&self) -> H::RelevantEventSelector {
         self.get_relevant_event_selector()
     }
 
     fn get_noise_scale(&self) -> NoiseScale {
-        NoiseScale::Laplace(self.get_laplace_noise_scale())
+        self.get_noise_scale()
+    }
+
+    fn get_requested_privacy_parameters(
+        &self,
+    ) -> Option<RequestedPrivacyParameters> {
+        self.get_requested_privacy_parameters()
+    }
+
+    fn get_deduplication_key(&self) -> Option<u64> {
+        self.get_deduplication_key()
+    }
+
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        HistogramRequest::validate(self)
     }
 
     /// Computes the report by attributing values to events, and then summing
     /// events by bucket.
     fn compute_report(
         &self,
-        relevant_events_per_epoch: &HashMap<Self::EpochId, Self::EpochEvents>,
+        relevant_events_per_epoch: &RelevantEventsPerEpoch<
+            Self::EpochId,
+            Self::EpochEvents,
+        >,
     ) -> Self::Report {
         let mut bin_values: HashMap<H::BucketKey, f64> = HashMap::new();
         let mut total_value: f64 = 0.0;
@@ -112,15 +234,20 @@ impl<H: HistogramRequest> EpochReportRequest for H {
 
         // The order matters, since events that are attributed last might be
         // dropped by the contribution cap.
-        //
-        // TODO(https://github.com/columbia/pdslib/issues/19):  Use an ordered map for relevant_events_per_epoch?
         for (event, value) in event_values {
+            let bin = self.get_bucket_key(event);
+            if let Some(possible_bucket_keys) = self.get_possible_bucket_keys()
+            {
+                if !possible_bucket_keys.contains(&bin) {
+                    continue;
+                }
+            }
+
             total_value += value;
             if total_value > self.get_attributable_value() {
                 // Return partial attribution to stay within the cap.
                 return HistogramReport { bin_values };
             }
-            let bin = self.get_bucket_key(event);
             *bin_values.entry(bin).or_default() += value;
         }
 
@@ -143,15 +270,77 @@ impl<H: HistogramRequest> EpochReportRequest for H {
         }
     }
 
+    fn get_report_distance(
+        &self,
+        report: &Self::Report,
+        other_report: &Self::Report,
+        norm_type: NormType,
+    ) -> Option<f64> {
+        Some(get_histogram_distance(report, other_report, norm_type))
+    }
+
     /// Computes the global sensitivity, useful for the multi-epoch case.
     /// See https://arxiv.org/pdf/2405.16719, Thm. 18
     fn get_report_global_sensitivity(&self) -> f64 {
-        // NOTE: if we have only one possible bin (histogram in R instead or
-        // R^m), then we can remove the factor 2. But this constraint is
-        // not enforceable with HashMap<BucketKey, f64>, so for
-        // use-cases that have one bin we should use a custom type
-        // similar to `SimpleLastTouchHistogramReport` with Option<BucketKey,
-        // f64>.
-        2.0 * self.get_attributable_value()
+        match self.get_possible_bucket_keys() {
+            // With only one possible bin (histogram in R instead of R^m), two
+            // reports differ by at most the attributable value. This is
+            // enforced by `compute_report`, which drops other bins.
+            Some(possible_bucket_keys) if possible_bucket_keys.len() <= 1 => {
+                self.get_attributable_value()
+            }
+
+            // Otherwise, two reports can put their whole value in different
+            // bins.
+            _ => 2.0 * self.get_attributable_value(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn test_aggregate_with_laplace_noise() {
+        let reports = [
+            HistogramReport {
+                bin_values: HashMap::from([(1_usize, 2.0), (2, 1.0)]),
+            },
+            HistogramReport {
+                bin_values: HashMap::from([(1, 3.0), (4, 7.0)]),
+            },
+        ];
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let sums =
+            aggregate_with_laplace_noise(&reports, &[1, 2, 3], 0.0, &mut rng);
+        assert_eq!(sums, HashMap::from([(1, 5.0), (2, 1.0), (3, 0.0)]));
+
+        let noisy_sums =
+            aggregate_with_laplace_noise(&reports, &[1, 2, 3], 1.0, &mut rng);
+        assert_eq!(noisy_sums.len(), 3);
+        assert!(noisy_sums.values().all(|value| *value != 0.0));
+    }
+
+    #[test]
+    fn test_histogram_distance() {
+        let report = HistogramReport {
+            bin_values: HashMap::from([(1_usize, 3.0), (2, 1.0)]),
+        };
+        let other_report = HistogramReport {
+            bin_values: HashMap::from([(2, 2.0), (3, 4.0)]),
+        };
+        assert_eq!(
+            get_histogram_distance(&report, &other_report, NormType::L1),
+            8.0
+        );
+        assert_eq!(
+            get_histogram_distance(&report, &other_report, NormType::L2),
+            26.0_f64.sqrt()
+        );
+        assert_eq!(get_histogram_distance(&report, &report, NormType::L1), 0.0);
     }
 }
@@ -0,0 +1,88 @@
+//! Time-based attribution windows, that select events with a precision of one
+//! second even though budget is still accounted per epoch.
+
+use crate::events::{
+    epoch_clock::EpochClock,
+    traits::{Event, RelevantEventSelector},
+};
+
+/// Time window that ends at the trigger and lasts `duration` seconds, both
+/// ends included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LookbackWindow {
+    /// Trigger time, in seconds since the Unix epoch.
+    pub trigger_time: u64,
+
+    /// Window length, in seconds.
+    pub duration: u64,
+}
+
+impl LookbackWindow {
+    /// Whether an event that happened at `timestamp` is in the window.
+    pub fn contains(&self, timestamp: u64) -> bool {
+        timestamp <= self.trigger_time
+            && self.trigger_time - timestamp <= self.duration
+    }
+
+    /// Returns the epochs that overlap with the window, most recent first.
+    /// The first and last epochs can be only partially covered, in which
+    /// case their events outside of the window are not relevant.
+    pub fn get_epoch_ids(&self, epoch_clock: &EpochClock) -> Vec<usize> {
+        let start = self.trigger_time.saturating_sub(self.duration);
+        epoch_clock.get_epoch_ids(start, self.trigger_time)
+    }
+}
+
+/// Selector that only keeps the events of `selector` that are in
+/// `lookback_window`, if any. Filtering happens when events are retrieved,
+/// so an epoch whose relevant events are all out of the window is treated as
+/// an epoch without relevant events and isn't charged.
+#[derive(Debug, Clone)]
+pub struct LookbackWindowSelector<RES> {
+    pub selector: RES,
+    pub lookback_window: Option<LookbackWindow>,
+}
+
+impl<RES: RelevantEventSelector> RelevantEventSelector
+    for LookbackWindowSelector<RES>
+{
+    type Event = RES::Event;
+
+    fn is_relevant_event(&self, event: &RES::Event) -> bool {
+        let in_window = match &self.lookback_window {
+            Some(lookback_window) => {
+                lookback_window.contains(event.get_timestamp())
+            }
+            None => true,
+        };
+        in_window && self.selector.is_relevant_event(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookback_window() {
+        let window = LookbackWindow {
+            trigger_time: 250,
+            duration: 100,
+        };
+        assert!(!window.contains(149));
+        assert!(window.contains(150));
+        assert!(window.contains(250));
+        assert!(!window.contains(251));
+
+        let clock = EpochClock::new(100).unwrap();
+        assert_eq!(window.get_epoch_ids(&clock), vec![2, 1]);
+
+        // Windows longer than the time since the Unix epoch are truncated.
+        let window = LookbackWindow {
+            trigger_time: 50,
+            duration: 100,
+        };
+        assert!(window.contains(0));
+        assert_eq!(window.get_epoch_ids(&clock), vec![0]);
+    }
+}
@@ -1,4 +1,8 @@
+pub mod ara_event_level;
 pub mod ara_histogram;
+pub mod ara_packed_histogram;
+pub mod attribution_histogram;
 pub mod histogram;
+pub mod lookback_window;
 pub mod simple_last_touch_histogram;
 pub mod traits;
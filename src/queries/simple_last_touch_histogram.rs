@@ -1,24 +1,27 @@
-use std::collections::HashMap;
-
 use crate::{
-    budget::pure_dp_filter::PureDPBudget,
     events::{
         hashmap_event_storage::VecEpochEvents, simple_event::SimpleEvent,
         traits::RelevantEventSelector,
     },
     mechanisms::{NoiseScale, NormType},
-    queries::traits::{EpochReportRequest, Report, ReportRequest},
+    queries::traits::{
+        EpochReportRequest, RelevantEventsPerEpoch, Report, ReportRequest,
+        RequestedPrivacyParameters,
+    },
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SimpleLastTouchHistogramRequest {
     pub epoch_start: usize,
     pub epoch_end: usize,
     pub attributable_value: f64,
     pub laplace_noise_scale: f64,
+    pub privacy_parameters: Option<RequestedPrivacyParameters>,
+    pub querier_id: usize,
     pub is_relevant_event: fn(&SimpleEvent) -> bool,
 }
 
+#[derive(Debug, Clone)]
 pub struct SimpleRelevantEventSelector {
     pub lambda: fn(&SimpleEvent) -> bool,
 }
@@ -49,7 +52,7 @@ impl ReportRequest for SimpleLastTouchHistogramRequest {
 impl EpochReportRequest for SimpleLastTouchHistogramRequest {
     type EpochId = usize;
     type EpochEvents = VecEpochEvents<SimpleEvent>;
-    type PrivacyBudget = PureDPBudget;
+    type QuerierId = usize;
     type ReportGlobalSensitivity = f64;
     type RelevantEventSelector = SimpleRelevantEventSelector;
 
@@ -58,6 +61,10 @@ impl EpochReportRequest for SimpleLastTouchHistogramRequest {
         range.rev().collect()
     }
 
+    fn get_querier_id(&self) -> usize {
+        self.querier_id
+    }
+
     fn get_relevant_event_selector(&self) -> Self::RelevantEventSelector {
         SimpleRelevantEventSelector {
             lambda: self.is_relevant_event,
@@ -66,7 +73,10 @@ impl EpochReportRequest for SimpleLastTouchHistogramRequest {
 
     fn compute_report(
         &self,
-        relevant_epochs_per_epoch: &HashMap<usize, Self::EpochEvents>,
+        relevant_epochs_per_epoch: &RelevantEventsPerEpoch<
+            usize,
+            Self::EpochEvents,
+        >,
     ) -> Self::Report {
         // Browse epochs in the order given by `get_epoch_ids, most recent
         // epoch first. Within each epoch, we assume that events are
@@ -115,7 +125,37 @@ impl EpochReportRequest for SimpleLastTouchHistogramRequest {
         self.attributable_value
     }
 
+    /// Reports have at most one bin each, so they differ either in the value
+    /// of a single bin or in two bins.
+    fn get_report_distance(
+        &self,
+        report: &Self::Report,
+        other_report: &Self::Report,
+        norm_type: NormType,
+    ) -> Option<f64> {
+        let distance = match (report.bin_value, other_report.bin_value) {
+            (None, None) => 0.0,
+            (Some((_, value)), None) | (None, Some((_, value))) => value.abs(),
+            (Some((key, value)), Some((other_key, other_value)))
+                if key == other_key =>
+            {
+                (value - other_value).abs()
+            }
+            (Some((_, value)), Some((_, other_value))) => match norm_type {
+                NormType::L1 => value.abs() + other_value.abs(),
+                NormType::L2 => value.hypot(other_value),
+            },
+        };
+        Some(distance)
+    }
+
     fn get_noise_scale(&self) -> NoiseScale {
         NoiseScale::Laplace(self.laplace_noise_scale)
     }
+
+    fn get_requested_privacy_parameters(
+        &self,
+    ) -> Option<RequestedPrivacyParameters> {
+        self.privacy_parameters
+    }
 }
@@ -1,4 +1,6 @@
-use std::{collections::HashMap, fmt::Debug};
+use std::{fmt::Debug, hash::Hash};
+
+use anyhow::bail;
 
 use crate::{
     events::traits::{EpochEvents, EpochId},
@@ -13,6 +15,174 @@ use crate::{
 /// TODO(https://github.com/columbia/pdslib/issues/20): marker trait for now, might add aggregation methods later.
 pub trait Report: Debug + Default {}
 
+/// Marker trait with bounds for querier identifiers, e.g. advertisers or
+/// reporting origins.
+pub trait QuerierId: Hash + std::cmp::Eq + Clone + Debug {}
+
+/// Default QuerierId
+impl QuerierId for usize {}
+
+/// QuerierId for URL-like identifiers, e.g. ARA reporting origins.
+impl QuerierId for String {}
+
+/// Relevant events for each epoch, in the order in which the attribution
+/// should browse them, i.e. the order of `get_epoch_ids`. Unlike a `HashMap`,
+/// iterating over epochs is deterministic, so reports that are truncated by a
+/// contribution cap are reproducible across runs and platforms.
+///
+/// Requests only cover a handful of epochs, so lookups are linear.
+#[derive(Debug, Clone)]
+pub struct RelevantEventsPerEpoch<EI, EE> {
+    epochs: Vec<(EI, EE)>,
+}
+
+impl<EI: PartialEq, EE> RelevantEventsPerEpoch<EI, EE> {
+    pub fn new() -> Self {
+        Self { epochs: vec![] }
+    }
+
+    /// Appends the events of `epoch_id` after the epochs that are already
+    /// present, or replaces them in place if the epoch is already present.
+    pub fn insert(&mut self, epoch_id: EI, epoch_events: EE) {
+        match self.epochs.iter_mut().find(|(id, _)| *id == epoch_id) {
+            Some((_, events)) => *events = epoch_events,
+            None => self.epochs.push((epoch_id, epoch_events)),
+        }
+    }
+
+    pub fn get(&self, epoch_id: &EI) -> Option<&EE> {
+        self.epochs
+            .iter()
+            .find(|(id, _)| id == epoch_id)
+            .map(|(_, events)| events)
+    }
+
+    /// Removes the events of `epoch_id`, keeping the order of other epochs.
+    pub fn remove(&mut self, epoch_id: &EI) -> Option<EE> {
+        let index = self.epochs.iter().position(|(id, _)| id == epoch_id)?;
+        Some(self.epochs.remove(index).1)
+    }
+
+    /// Number of epochs with relevant events.
+    pub fn len(&self) -> usize {
+        self.epochs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.epochs.is_empty()
+    }
+
+    /// Calls `f` without the events of `epoch_id`, and puts them back at the
+    /// same position afterwards.
+    pub fn with_epoch_removed<T>(
+        &mut self,
+        epoch_id: &EI,
+        f: impl FnOnce(&Self) -> T,
+    ) -> T {
+        match self.epochs.iter().position(|(id, _)| id == epoch_id) {
+            Some(index) => {
+                let epoch = self.epochs.remove(index);
+                let result = f(self);
+                self.epochs.insert(index, epoch);
+                result
+            }
+            None => f(self),
+        }
+    }
+
+    /// Iterates over epochs in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&EI, &EE)> {
+        self.epochs.iter().map(|(id, events)| (id, events))
+    }
+
+    /// Iterates over the events of each epoch, in order.
+    pub fn values(&self) -> impl Iterator<Item = &EE> {
+        self.epochs.iter().map(|(_, events)| events)
+    }
+}
+
+impl<EI: PartialEq, EE> Default for RelevantEventsPerEpoch<EI, EE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<EI: PartialEq, EE> FromIterator<(EI, EE)>
+    for RelevantEventsPerEpoch<EI, EE>
+{
+    fn from_iter<I: IntoIterator<Item = (EI, EE)>>(iter: I) -> Self {
+        let mut relevant_events_per_epoch = Self::new();
+        for (epoch_id, epoch_events) in iter {
+            relevant_events_per_epoch.insert(epoch_id, epoch_events);
+        }
+        relevant_events_per_epoch
+    }
+}
+
+/// Privacy parameters that a querier declares for the whole query, as in
+/// Cookie Monster: the aggregator adds noise calibrated to
+/// `global_sensitivity / requested_epsilon`, where `global_sensitivity` bounds
+/// the contribution of any device to the aggregate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RequestedPrivacyParameters {
+    pub requested_epsilon: f64,
+    pub global_sensitivity: f64,
+}
+
+impl RequestedPrivacyParameters {
+    /// Checks that the parameters are valid and that `noise_scale` is the
+    /// noise they imply, up to floating point errors.
+    pub fn validate(
+        &self,
+        noise_scale: &NoiseScale,
+    ) -> Result<(), anyhow::Error> {
+        let is_positive = |x: f64| x > 0.0 && x.is_finite();
+        if !is_positive(self.requested_epsilon)
+            || !is_positive(self.global_sensitivity)
+        {
+            bail!("Invalid privacy parameters {:?}", self);
+        }
+
+        let matches =
+            |x: f64, expected: f64| (x - expected).abs() <= 1e-9 * expected;
+        match noise_scale {
+            NoiseScale::Laplace(laplace_scale) => {
+                let expected_scale =
+                    self.global_sensitivity / self.requested_epsilon;
+                if !matches(*laplace_scale, expected_scale) {
+                    bail!(
+                        "Laplace scale {} doesn't match the privacy parameters {:?}",
+                        laplace_scale,
+                        self
+                    );
+                }
+            }
+            // Randomized response reports have sensitivity 1.
+            NoiseScale::RandomizedResponse(epsilon) => {
+                if !matches(*epsilon, self.requested_epsilon)
+                    || self.global_sensitivity != 1.0
+                {
+                    bail!(
+                        "Randomized response epsilon {} doesn't match the privacy parameters {:?}",
+                        epsilon,
+                        self
+                    );
+                }
+            }
+            NoiseScale::Gaussian(_) => {
+                bail!("Gaussian noise doesn't have a pure DP epsilon")
+            }
+        }
+        Ok(())
+    }
+
+    /// Pure DP loss of a report with sensitivity `individual_sensitivity`.
+    pub fn get_individual_epsilon(&self, individual_sensitivity: f64) -> f64 {
+        individual_sensitivity * self.requested_epsilon
+            / self.global_sensitivity
+    }
+}
+
 /// Trait for a generic query.
 pub trait ReportRequest: Debug {
     type Report: Report;
@@ -22,22 +192,30 @@ pub trait ReportRequest: Debug {
 pub trait EpochReportRequest: ReportRequest {
     type EpochId: EpochId;
     type EpochEvents: EpochEvents;
+    type QuerierId: QuerierId;
     type RelevantEventSelector;
-    type PrivacyBudget;
     type ReportGlobalSensitivity;
 
     /// Returns the list of requested epoch IDs, in the order the attribution
     /// should run.
     fn get_epoch_ids(&self) -> Vec<Self::EpochId>;
 
+    /// Returns the identifier of the querier issuing the request. The PDS
+    /// keeps separate filters for each querier.
+    fn get_querier_id(&self) -> Self::QuerierId;
+
     /// Returns the selector for relevant events for the query. The selector
     /// can be passed to the event storage to retrieve only the relevant events.
     fn get_relevant_event_selector(&self) -> Self::RelevantEventSelector;
 
-    /// Computes the report for the given request and epoch events.
+    /// Computes the report for the given request and epoch events, which are
+    /// given in the order of `get_epoch_ids`.
     fn compute_report(
         &self,
-        relevant_events_per_epoch: &HashMap<Self::EpochId, Self::EpochEvents>,
+        relevant_events_per_epoch: &RelevantEventsPerEpoch<
+            Self::EpochId,
+            Self::EpochEvents,
+        >,
     ) -> Self::Report;
 
     /// Computes the individual sensitivity for the query when the report is
@@ -51,13 +229,120 @@ pub trait EpochReportRequest: ReportRequest {
     /// Computes the global sensitivity for the query.
     fn get_report_global_sensitivity(&self) -> f64;
 
+    /// Returns the distance between two reports in `norm_type`, used for
+    /// leave-one-epoch-out sensitivities. `None` if the request can't compare
+    /// reports, in which case the global sensitivity is used instead.
+    fn get_report_distance(
+        &self,
+        _report: &Self::Report,
+        _other_report: &Self::Report,
+        _norm_type: NormType,
+    ) -> Option<f64> {
+        None
+    }
+
     /// Retrieves the scale of the noise that will be added by the aggregator.
     fn get_noise_scale(&self) -> NoiseScale;
+
+    /// Checks that the request is well-formed, e.g. that its values are in
+    /// range. The PDS rejects invalid requests with an error before consuming
+    /// any budget. Requests are valid by default.
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
+    /// Returns the epsilon and global sensitivity of the query, if the
+    /// querier declares them. The PDS then rejects requests whose noise scale
+    /// doesn't match, and charges losses computed from these parameters.
+    fn get_requested_privacy_parameters(
+        &self,
+    ) -> Option<RequestedPrivacyParameters> {
+        None
+    }
+
+    /// Randomizes the final report on device, for mechanisms that add noise
+    /// before the report leaves the device such as randomized response. Only
+    /// applied to the returned report, after budget has been consumed. Does
+    /// nothing by default, i.e. noise is added by the aggregator.
+    fn perturb_report(&self, report: Self::Report) -> Self::Report {
+        report
+    }
+
+    /// Returns a key that identifies the trigger of the request, if any. The
+    /// PDS computes at most one report per deduplication key and querier,
+    /// and returns null reports for duplicates.
+    fn get_deduplication_key(&self) -> Option<u64> {
+        None
+    }
 }
 
 /// Type for passive privacy loss accounting. Uniform over all epochs for now.
 #[derive(Debug)]
-pub struct PassivePrivacyLossRequest<EI: EpochId, PrivacyBudget> {
+pub struct PassivePrivacyLossRequest<EI: EpochId, QI: QuerierId, PrivacyBudget>
+{
     pub epoch_ids: Vec<EI>,
+    pub querier_id: QI,
     pub privacy_budget: PrivacyBudget,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relevant_events_per_epoch_order() {
+        let mut relevant_events_per_epoch =
+            RelevantEventsPerEpoch::from_iter([(3, "c"), (1, "a"), (2, "b")]);
+        relevant_events_per_epoch.insert(1, "a2");
+        assert_eq!(relevant_events_per_epoch.remove(&3), Some("c"));
+        assert_eq!(relevant_events_per_epoch.remove(&3), None);
+        relevant_events_per_epoch.insert(4, "d");
+
+        let epochs: Vec<(&usize, &&str)> =
+            relevant_events_per_epoch.iter().collect();
+        assert_eq!(epochs, vec![(&1, &"a2"), (&2, &"b"), (&4, &"d")]);
+        assert_eq!(relevant_events_per_epoch.get(&2), Some(&"b"));
+        assert_eq!(relevant_events_per_epoch.len(), 3);
+
+        let epochs = relevant_events_per_epoch
+            .with_epoch_removed(&2, |events| {
+                events.iter().map(|(id, _)| *id).collect::<Vec<_>>()
+            });
+        assert_eq!(epochs, vec![1, 4]);
+        assert_eq!(relevant_events_per_epoch.get(&2), Some(&"b"));
+        assert_eq!(relevant_events_per_epoch.iter().nth(1), Some((&2, &"b")));
+    }
+
+    #[test]
+    fn test_requested_privacy_parameters() {
+        let privacy_parameters = RequestedPrivacyParameters {
+            requested_epsilon: 0.5,
+            global_sensitivity: 10.0,
+        };
+        assert!(privacy_parameters
+            .validate(&NoiseScale::Laplace(20.0))
+            .is_ok());
+        assert!(privacy_parameters
+            .validate(&NoiseScale::Laplace(10.0))
+            .is_err());
+        assert!(privacy_parameters
+            .validate(&NoiseScale::Gaussian(20.0))
+            .is_err());
+        assert_eq!(privacy_parameters.get_individual_epsilon(4.0), 0.2);
+
+        let privacy_parameters = RequestedPrivacyParameters {
+            requested_epsilon: 0.5,
+            global_sensitivity: 1.0,
+        };
+        assert!(privacy_parameters
+            .validate(&NoiseScale::RandomizedResponse(0.5))
+            .is_ok());
+        let invalid_parameters = RequestedPrivacyParameters {
+            requested_epsilon: 0.0,
+            global_sensitivity: 1.0,
+        };
+        assert!(invalid_parameters
+            .validate(&NoiseScale::Laplace(f64::INFINITY))
+            .is_err());
+    }
+}
@@ -4,36 +4,97 @@ use pdslib::{
     budget::{
         hashmap_filter_storage::HashMapFilterStorage,
         pure_dp_filter::{PureDPBudget, PureDPBudgetFilter},
+        traits::FilterStorage,
+    },
+    events::{
+        ara_event::{
+            parse_aggregation_keys, parse_key_piece, AraEvent, SourceType,
+        },
+        epoch_clock::EpochClock,
+        hashmap_event_storage::HashMapEventStorage,
+    },
+    pds::{
+        epoch_pds::{EpochPrivateDataService, FilterId},
+        hashmap_deduplication_key_storage::HashMapDeduplicationKeyStorage,
+    },
+    queries::{
+        ara_event_level::{AraEventLevelReport, AraEventLevelRequest},
+        ara_histogram::{
+            AraFilter, AraHistogramRequest, AraRelevantEventSelector,
+        },
+        ara_packed_histogram::{AraPackedHistogramRequest, AraTriggerData},
+        traits::EpochReportRequest,
     },
-    events::{ara_event::AraEvent, hashmap_event_storage::HashMapEventStorage},
-    pds::epoch_pds::EpochPrivateDataService,
-    queries::ara_histogram::{AraHistogramRequest, AraRelevantEventSelector},
 };
 
+type Pds<Q> = EpochPrivateDataService<
+    HashMapFilterStorage<
+        FilterId<usize, String>,
+        PureDPBudgetFilter,
+        PureDPBudget,
+    >,
+    HashMapEventStorage<AraEvent, AraRelevantEventSelector>,
+    HashMapDeduplicationKeyStorage<usize, String>,
+    Q,
+    anyhow::Error,
+>;
+
+/// Creates a PDS with in-memory storage for ARA requests.
+fn new_pds<Q: EpochReportRequest>(
+    querier_capacity: PureDPBudget,
+    global_capacity: PureDPBudget,
+) -> Pds<Q> {
+    EpochPrivateDataService::new(
+        HashMapFilterStorage::new(),
+        HashMapEventStorage::new(),
+        HashMapDeduplicationKeyStorage::new(),
+        epoch_clock(),
+        querier_capacity,
+        global_capacity,
+    )
+}
+
+/// Epochs of 1000 seconds.
+fn epoch_clock() -> EpochClock {
+    EpochClock::new(1_000).unwrap()
+}
+
+/// Navigation source registered at the start of `epoch_number`.
+fn source(
+    id: usize,
+    epoch_number: usize,
+    aggregatable_sources: HashMap<String, u128>,
+) -> AraEvent {
+    let clock = epoch_clock();
+    AraEvent::new(
+        id,
+        aggregatable_sources,
+        SourceType::Navigation,
+        clock.get_epoch_start(epoch_number),
+        &clock,
+    )
+}
+
 #[test]
 fn main() {
-    let events =
-        HashMapEventStorage::<AraEvent, AraRelevantEventSelector>::new();
-    let filters: HashMapFilterStorage<usize, PureDPBudgetFilter, PureDPBudget> =
-        HashMapFilterStorage::new();
-
-    let mut pds = EpochPrivateDataService {
-        filter_storage: filters,
-        event_storage: events,
-        epoch_capacity: PureDPBudget::Epsilon(3.0),
-        _phantom_request: std::marker::PhantomData::<AraHistogramRequest>,
-        _phantom_error: std::marker::PhantomData::<anyhow::Error>,
-    };
+    let mut pds = new_pds::<AraHistogramRequest>(
+        PureDPBudget::Epsilon(3.0),
+        PureDPBudget::Epsilon(20.0),
+    );
 
     // Test similar to https://github.com/WICG/attribution-reporting-api/blob/main/AGGREGATE.md#attribution-trigger-registration
-    let mut sources1 = HashMap::new();
-    sources1.insert("campaignCounts".to_string(), 0x159);
-    sources1.insert("geoValue".to_string(), 0x5);
+    let aggregation_keys = HashMap::from([
+        ("campaignCounts".to_string(), "0x159".to_string()),
+        ("geoValue".to_string(), "0x5".to_string()),
+    ]);
+    let sources1 = parse_aggregation_keys(&aggregation_keys).unwrap();
 
     let event1 = AraEvent {
-        id: 1,
-        epoch_number: 1,
-        aggregatable_sources: sources1,
+        filter_data: HashMap::from([(
+            "campaign".to_string(),
+            vec!["summer".to_string()],
+        )]),
+        ..source(1, 1, sources1)
     };
 
     pds.register_event(event1.clone()).unwrap();
@@ -45,11 +106,12 @@ fn main() {
         per_event_attributable_value: 32768.0,
         attributable_value: 65536.0,
         noise_scale: 65536.0,
+        privacy_parameters: None,
+        querier_id: "https://ad-tech.example".to_string(),
         source_key: "campaignCounts".to_string(),
-        trigger_keypiece: 0x400,
-        filters: AraRelevantEventSelector {
-            filters: HashMap::new(),
-        }, // Not filtering yet.
+        trigger_keypiece: parse_key_piece("0x400").unwrap(),
+        filters: AraRelevantEventSelector::default(),
+        deduplication_key: None,
     };
 
     let report1 = pds.compute_report(request1).unwrap();
@@ -60,5 +122,293 @@ fn main() {
     assert!(report1.bin_values.contains_key(&0x559));
     assert_eq!(report1.bin_values.get(&0x559), Some(&32768.0));
 
+    // A trigger for another campaign doesn't match the event.
+    let request2 = AraHistogramRequest {
+        start_epoch: 1,
+        end_epoch: 2,
+        per_event_attributable_value: 32768.0,
+        attributable_value: 65536.0,
+        noise_scale: 65536.0,
+        privacy_parameters: None,
+        querier_id: "https://ad-tech.example".to_string(),
+        source_key: "campaignCounts".to_string(),
+        trigger_keypiece: parse_key_piece("0x400").unwrap(),
+        filters: AraRelevantEventSelector {
+            filters: vec![AraFilter {
+                values: HashMap::from([(
+                    "campaign".to_string(),
+                    vec!["winter".to_string()],
+                )]),
+                lookback_window: None,
+            }],
+            not_filters: vec![],
+            trigger_time: 2_000,
+        },
+        deduplication_key: None,
+    };
+    let report2 = pds.compute_report(request2).unwrap();
+    assert!(report2.bin_values.is_empty());
+
     // TODO(https://github.com/columbia/pdslib/issues/8): add more tests when we have multiple events
 }
+
+#[test]
+fn packed_request() {
+    let mut pds = new_pds::<AraPackedHistogramRequest>(
+        PureDPBudget::Epsilon(3.0),
+        PureDPBudget::Epsilon(20.0),
+    );
+
+    let aggregation_keys = HashMap::from([
+        ("campaignCounts".to_string(), "0x159".to_string()),
+        ("geoValue".to_string(), "0x5".to_string()),
+    ]);
+    let event =
+        source(1, 1, parse_aggregation_keys(&aggregation_keys).unwrap());
+    pds.register_event(event).unwrap();
+
+    let request = |geo_value| AraPackedHistogramRequest {
+        start_epoch: 1,
+        end_epoch: 1,
+        attributable_value: 65536.0,
+        noise_scale: 65536.0,
+        querier_id: "https://ad-tech.example".to_string(),
+        aggregatable_trigger_data: vec![
+            AraTriggerData {
+                key_piece: parse_key_piece("0x400").unwrap(),
+                source_keys: vec!["campaignCounts".to_string()],
+            },
+            AraTriggerData {
+                key_piece: parse_key_piece("0xA80").unwrap(),
+                source_keys: vec!["geoValue".to_string()],
+            },
+        ],
+        aggregatable_values: HashMap::from([
+            ("campaignCounts".to_string(), 32768.0),
+            ("geoValue".to_string(), geo_value),
+        ]),
+        filters: AraRelevantEventSelector::default(),
+        deduplication_key: None,
+    };
+    let report = pds.compute_report(request(1664.0)).unwrap();
+    assert_eq!(report.bin_values.get(&0x559), Some(&32768.0));
+    assert_eq!(report.bin_values.get(&0xa85), Some(&1664.0));
+
+    // Both contributions are paid for in a single accounting pass, with the
+    // L1 norm of the whole report.
+    let remaining = pds
+        .filter_storage
+        .get_remaining_budget(&FilterId::Querier(
+            1,
+            "https://ad-tech.example".to_string(),
+        ))
+        .unwrap();
+    assert_eq!(
+        remaining,
+        PureDPBudget::Epsilon(3.0 - (32768.0 + 1664.0) / 65536.0)
+    );
+
+    // NaN or negative values would bypass the contribution cap, so they are
+    // rejected before consuming any budget.
+    assert!(pds.compute_report(request(f64::NAN)).is_err());
+    assert!(pds.compute_report(request(-1664.0)).is_err());
+    let remaining = pds
+        .filter_storage
+        .get_remaining_budget(&FilterId::Querier(
+            1,
+            "https://ad-tech.example".to_string(),
+        ))
+        .unwrap();
+    assert_eq!(
+        remaining,
+        PureDPBudget::Epsilon(3.0 - (32768.0 + 1664.0) / 65536.0)
+    );
+}
+
+#[test]
+fn capped_report_is_deterministic() {
+    let mut pds = new_pds::<AraHistogramRequest>(
+        PureDPBudget::Infinite,
+        PureDPBudget::Infinite,
+    );
+
+    // One event per epoch, each with a different bucket.
+    for epoch_number in 1..=8 {
+        let sources = HashMap::from([(
+            "campaignCounts".to_string(),
+            epoch_number as u128,
+        )]);
+        let event = source(epoch_number, epoch_number, sources);
+        pds.register_event(event).unwrap();
+    }
+
+    // Only three events fit under the cap, which must always be the events
+    // from the three most recent epochs.
+    for _ in 0..200 {
+        let request = AraHistogramRequest {
+            start_epoch: 1,
+            end_epoch: 8,
+            per_event_attributable_value: 1.0,
+            attributable_value: 3.0,
+            noise_scale: 1.0,
+            privacy_parameters: None,
+            querier_id: "https://ad-tech.example".to_string(),
+            source_key: "campaignCounts".to_string(),
+            trigger_keypiece: 0x100,
+            filters: AraRelevantEventSelector::default(),
+            deduplication_key: None,
+        };
+        let report = pds.compute_report(request).unwrap();
+        assert_eq!(
+            report.bin_values,
+            HashMap::from([(0x108, 1.0), (0x107, 1.0), (0x106, 1.0)])
+        );
+    }
+}
+
+#[test]
+fn deduplication_key() {
+    let mut pds = new_pds::<AraHistogramRequest>(
+        PureDPBudget::Epsilon(3.0),
+        PureDPBudget::Infinite,
+    );
+
+    let event = AraEvent {
+        debug_key: Some(42),
+        ..source(1, 1, HashMap::from([("campaignCounts".to_string(), 0x159)]))
+    };
+    pds.register_event(event).unwrap();
+
+    let request = |querier_id: &str, deduplication_key| AraHistogramRequest {
+        start_epoch: 1,
+        end_epoch: 1,
+        per_event_attributable_value: 1.0,
+        attributable_value: 1.0,
+        noise_scale: 1.0,
+        privacy_parameters: None,
+        querier_id: querier_id.to_string(),
+        source_key: "campaignCounts".to_string(),
+        trigger_keypiece: 0x400,
+        filters: AraRelevantEventSelector::default(),
+        deduplication_key,
+    };
+    let remaining = |filters: &HashMapFilterStorage<_, _, _>,
+                     querier_id: &str| {
+        filters
+            .get_remaining_budget(&FilterId::Querier(1, querier_id.to_string()))
+            .unwrap()
+    };
+
+    let report = pds
+        .compute_report(request("https://ad-tech.example", Some(7)))
+        .unwrap();
+    assert_eq!(report.bin_values, HashMap::from([(0x559, 1.0)]));
+    assert_eq!(
+        remaining(&pds.filter_storage, "https://ad-tech.example"),
+        PureDPBudget::Epsilon(2.0)
+    );
+
+    // The same trigger gets a null report, for free.
+    let report = pds
+        .compute_report(request("https://ad-tech.example", Some(7)))
+        .unwrap();
+    assert!(report.bin_values.is_empty());
+    assert_eq!(
+        remaining(&pds.filter_storage, "https://ad-tech.example"),
+        PureDPBudget::Epsilon(2.0)
+    );
+
+    // Other keys, requests without keys and other queriers are not affected.
+    for request in [
+        request("https://ad-tech.example", Some(8)),
+        request("https://ad-tech.example", None),
+        request("https://other-ad-tech.example", Some(7)),
+    ] {
+        let report = pds.compute_report(request).unwrap();
+        assert_eq!(report.bin_values, HashMap::from([(0x559, 1.0)]));
+    }
+
+    // A key is only recorded once its report is computed. Here the filters
+    // can't be created, so the first report fails and the key can be reused.
+    pds.querier_capacity = PureDPBudget::Epsilon(-1.0);
+    let report = pds
+        .compute_report(request("https://new-ad-tech.example", Some(7)))
+        .unwrap();
+    assert!(report.bin_values.is_empty());
+
+    pds.querier_capacity = PureDPBudget::Epsilon(3.0);
+    let report = pds
+        .compute_report(request("https://new-ad-tech.example", Some(7)))
+        .unwrap();
+    assert_eq!(report.bin_values, HashMap::from([(0x559, 1.0)]));
+
+    // Keys are recorded in the most recent requested epoch, here epoch 1, and
+    // are dropped with it, e.g. once it is out of the attribution window.
+    pds.delete_epochs_before(&2).unwrap();
+    let sources = HashMap::from([("campaignCounts".to_string(), 0x159)]);
+    pds.register_event(source(2, 2, sources)).unwrap();
+    let report = pds
+        .compute_report(AraHistogramRequest {
+            start_epoch: 2,
+            end_epoch: 2,
+            ..request("https://ad-tech.example", Some(7))
+        })
+        .unwrap();
+    assert_eq!(report.bin_values, HashMap::from([(0x559, 1.0)]));
+}
+
+#[test]
+fn event_level_report() {
+    let mut pds = new_pds::<AraEventLevelRequest>(
+        PureDPBudget::Epsilon(60.0),
+        PureDPBudget::Infinite,
+    );
+
+    let event = AraEvent {
+        source_type: SourceType::Event,
+        ..source(1, 1, HashMap::new())
+    };
+    pds.register_event(event).unwrap();
+
+    // Large epsilons, so randomized response (almost surely) keeps the
+    // report as it is.
+    let request = |end_epoch| AraEventLevelRequest {
+        start_epoch: 1,
+        end_epoch,
+        trigger_data: 1,
+        trigger_data_cardinality: 2,
+        epsilon: 40.0,
+        querier_id: "https://ad-tech.example".to_string(),
+        filters: AraRelevantEventSelector::default(),
+        deduplication_key: None,
+    };
+    let remaining = |filters: &HashMapFilterStorage<_, _, _>, epoch_id| {
+        filters
+            .get_remaining_budget(&FilterId::Querier(
+                epoch_id,
+                "https://ad-tech.example".to_string(),
+            ))
+            .unwrap()
+    };
+
+    // Only the epoch with the source pays the randomized response epsilon.
+    let report = pds.compute_report(request(2)).unwrap();
+    assert_eq!(report.trigger_data, Some(1));
+    assert_eq!(
+        remaining(&pds.filter_storage, 1),
+        PureDPBudget::Epsilon(20.0)
+    );
+    assert_eq!(
+        remaining(&pds.filter_storage, 2),
+        PureDPBudget::Epsilon(60.0)
+    );
+
+    // Once the epoch is out of budget, the source is dropped and the report
+    // is a null report.
+    let report = pds.compute_report(request(1)).unwrap();
+    assert_eq!(report, AraEventLevelReport::default());
+    assert_eq!(
+        remaining(&pds.filter_storage, 1),
+        PureDPBudget::Epsilon(20.0)
+    );
+}
@@ -2,12 +2,20 @@ use pdslib::{
     budget::{
         hashmap_filter_storage::HashMapFilterStorage,
         pure_dp_filter::{PureDPBudget, PureDPBudgetFilter},
+        traits::FilterStorage,
     },
     events::{
-        hashmap_event_storage::HashMapEventStorage, simple_event::SimpleEvent,
+        epoch_clock::EpochClock, hashmap_event_storage::HashMapEventStorage,
+        simple_event::SimpleEvent,
+    },
+    pds::{
+        epoch_pds::{EpochPrivateDataService, FilterId},
+        hashmap_deduplication_key_storage::HashMapDeduplicationKeyStorage,
+    },
+    queries::{
+        simple_last_touch_histogram::SimpleLastTouchHistogramRequest,
+        traits::RequestedPrivacyParameters,
     },
-    pds::epoch_pds::EpochPrivateDataService,
-    queries::simple_last_touch_histogram::SimpleLastTouchHistogramRequest,
 };
 
 #[test]
@@ -18,38 +26,42 @@ fn main() {
 
     // Set up storage and Private Data Service.
     let events = HashMapEventStorage::new();
-    let filters: HashMapFilterStorage<usize, PureDPBudgetFilter, PureDPBudget> =
-        HashMapFilterStorage::new();
+    let filters: HashMapFilterStorage<
+        FilterId<usize, usize>,
+        PureDPBudgetFilter,
+        PureDPBudget,
+    > = HashMapFilterStorage::new();
 
-    let mut pds = EpochPrivateDataService {
-        filter_storage: filters,
-        event_storage: events,
-        epoch_capacity: PureDPBudget::Epsilon(3.0),
-        _phantom_request: std::marker::PhantomData::<
-            SimpleLastTouchHistogramRequest,
-        >,
-        _phantom_error: std::marker::PhantomData::<anyhow::Error>,
-    };
+    // Epochs last 100 seconds.
+    let epoch_clock = EpochClock::new(100).unwrap();
+    let mut pds: EpochPrivateDataService<_, _, _, _, anyhow::Error> =
+        EpochPrivateDataService::new(
+            filters,
+            events,
+            HashMapDeduplicationKeyStorage::new(),
+            epoch_clock,
+            PureDPBudget::Epsilon(3.0),
+            PureDPBudget::Epsilon(20.0),
+        );
 
-    // Create an impression (event, with very basic metadata).
-    let event = SimpleEvent {
-        id: 1,
-        epoch_number: 1,
-        event_key: 3,
-    };
+    // Create an impression (event, with very basic metadata) at time 150, in
+    // epoch 1.
+    let event = SimpleEvent::new(1, 150, 3, &epoch_clock);
 
     // Save impression.
     pds.register_event(event.clone()).unwrap();
 
     // Next, a conversion happens and the querier prepares request parameters.
 
-    // pdslib only needs the mechanism (noise distribution and scale), which
-    // can be computed from the global sensitivity and global epsilon if needed.
-    // TODO(https://github.com/columbia/pdslib/issues/23): potentially use two parameters
-    // instead of a single `laplace_noise_scale`.
-    let query_global_sensitivity = 100.0;
-    let requested_epsilon = 1.0;
-    let laplace_noise_scale = query_global_sensitivity / requested_epsilon;
+    // The querier declares the global sensitivity of the query and the
+    // epsilon it requests, and the noise that they imply. pdslib checks that
+    // they match, and charges losses computed from the declared parameters.
+    let privacy_parameters = RequestedPrivacyParameters {
+        requested_epsilon: 1.0,
+        global_sensitivity: 100.0,
+    };
+    let laplace_noise_scale = privacy_parameters.global_sensitivity
+        / privacy_parameters.requested_epsilon;
 
     // Can depend on information available to the querier about this particular
     // conversion.
@@ -65,12 +77,37 @@ fn main() {
         epoch_end: 4,
         attributable_value: report_global_sensitivity,
         laplace_noise_scale,
+        privacy_parameters: Some(privacy_parameters),
+        querier_id: 1,
         is_relevant_event,
     };
 
     // Measure conversion.
-    let report = pds.compute_report(report_request).unwrap();
+    let report = pds.compute_report(report_request.clone()).unwrap();
 
     // Look at the histogram stored in the report (unencrypted here).
     assert_eq!(report.bin_value, Some((event.event_key, 70.0)));
+
+    // Only epoch 1 has relevant events, so it pays the individual loss
+    // 70 * 1.0 / 100.
+    assert_eq!(
+        pds.filter_storage
+            .get_remaining_budget(&FilterId::Querier(1, 1))
+            .unwrap(),
+        PureDPBudget::Epsilon(2.3)
+    );
+
+    // Requests with noise that doesn't match their declared parameters are
+    // rejected, without consuming any budget.
+    let invalid_request = SimpleLastTouchHistogramRequest {
+        laplace_noise_scale: 10.0,
+        ..report_request
+    };
+    assert!(pds.compute_report(invalid_request).is_err());
+    assert_eq!(
+        pds.filter_storage
+            .get_remaining_budget(&FilterId::Querier(1, 1))
+            .unwrap(),
+        PureDPBudget::Epsilon(2.3)
+    );
 }
@@ -1,62 +1,66 @@
+use std::collections::{HashMap, HashSet};
+
 use pdslib::{
     budget::{
         hashmap_filter_storage::HashMapFilterStorage,
         pure_dp_filter::{PureDPBudget, PureDPBudgetFilter},
+        traits::FilterStorage,
+        zcdp_filter::{ZcdpBudget, ZcdpBudgetFilter},
     },
     events::{
-        hashmap_event_storage::HashMapEventStorage, simple_event::SimpleEvent,
+        epoch_clock::EpochClock, hashmap_event_storage::HashMapEventStorage,
+        simple_event::SimpleEvent,
+    },
+    mechanisms::NoiseScale,
+    pds::{
+        epoch_pds::{EpochPrivateDataService, FilterId},
+        hashmap_deduplication_key_storage::HashMapDeduplicationKeyStorage,
+    },
+    queries::{
+        attribution_histogram::{
+            AttributionHistogramRequest, AttributionModel,
+        },
+        lookback_window::LookbackWindow,
+        simple_last_touch_histogram::{
+            SimpleLastTouchHistogramRequest, SimpleRelevantEventSelector,
+        },
     },
-    pds::epoch_pds::EpochPrivateDataService,
-    queries::simple_last_touch_histogram::SimpleLastTouchHistogramRequest,
 };
 
 #[test]
 fn main() {
-    let events = HashMapEventStorage::new();
-    let filters: HashMapFilterStorage<usize, PureDPBudgetFilter, PureDPBudget> =
-        HashMapFilterStorage::new();
-
-    let mut pds = EpochPrivateDataService {
-        filter_storage: filters,
-        event_storage: events,
-        epoch_capacity: PureDPBudget::Epsilon(3.0),
-        _phantom_request: std::marker::PhantomData::<
-            SimpleLastTouchHistogramRequest,
-        >,
-        _phantom_error: std::marker::PhantomData::<anyhow::Error>,
-    };
+    type Filters = HashMapFilterStorage<
+        FilterId<usize, usize>,
+        PureDPBudgetFilter,
+        PureDPBudget,
+    >;
+    let mut pds: EpochPrivateDataService<_, _, _, _, anyhow::Error> =
+        EpochPrivateDataService::new(
+            Filters::new(),
+            HashMapEventStorage::new(),
+            HashMapDeduplicationKeyStorage::new(),
+            epoch_clock(),
+            PureDPBudget::Epsilon(3.0),
+            PureDPBudget::Epsilon(20.0),
+        );
 
-    let event = SimpleEvent {
-        id: 1,
-        epoch_number: 1,
-        event_key: 3,
-    };
-    let event2 = SimpleEvent {
-        id: 1,
-        epoch_number: 2,
-        event_key: 3,
-    };
-    let event3 = SimpleEvent {
-        id: 2,
-        epoch_number: 2,
-        event_key: 3,
-    };
-    let event4 = SimpleEvent {
-        id: 1,
-        epoch_number: 3,
-        event_key: 3,
-    };
+    let event1 = event(1, 1, 3);
+    let event2 = event(1, 2, 3);
+    let event3 = event(2, 2, 3);
+    let event4 = event(1, 3, 3);
 
-    pds.register_event(event.clone()).unwrap();
+    pds.register_event(event1.clone()).unwrap();
     let report_request = SimpleLastTouchHistogramRequest {
         epoch_start: 1,
         epoch_end: 1,
         attributable_value: 3.0,
         laplace_noise_scale: 1.0,
+        privacy_parameters: None,
+        querier_id: 1,
         is_relevant_event: always_relevant_event,
     };
     let report = pds.compute_report(report_request).unwrap();
-    let bucket = Some((event.event_key, 3.0));
+    let bucket = Some((event1.event_key, 3.0));
     assert_eq!(report.bin_value, bucket);
 
     // Test having multiple events in one epoch
@@ -69,6 +73,8 @@ fn main() {
                        * limit as the current budget left for
                        * epoch 1 is 0. */
         laplace_noise_scale: 1.0,
+        privacy_parameters: None,
+        querier_id: 1,
         is_relevant_event: always_relevant_event,
     };
     let report2 = pds.compute_report(report_request2).unwrap();
@@ -82,6 +88,8 @@ fn main() {
         epoch_end: 2,
         attributable_value: 3.0,
         laplace_noise_scale: 1.0,
+        privacy_parameters: None,
+        querier_id: 1,
         is_relevant_event: always_relevant_event,
     };
     let report2 = pds.compute_report(report_request2).unwrap();
@@ -94,6 +102,8 @@ fn main() {
         epoch_end: 3,   // Epoch 3 not created yet.
         attributable_value: 0.0,
         laplace_noise_scale: 1.0,
+        privacy_parameters: None,
+        querier_id: 1,
         is_relevant_event: always_relevant_event,
     };
     let report3_empty = pds.compute_report(report_request3_empty).unwrap();
@@ -106,6 +116,8 @@ fn main() {
         epoch_end: 3,
         attributable_value: 4.0,
         laplace_noise_scale: 1.0,
+        privacy_parameters: None,
+        querier_id: 1,
         is_relevant_event: always_relevant_event,
     };
     let report3_over_budget =
@@ -119,6 +131,8 @@ fn main() {
         epoch_end: 3,
         attributable_value: 3.0,
         laplace_noise_scale: 1.0,
+        privacy_parameters: None,
+        querier_id: 1,
         is_relevant_event: always_relevant_event,
     };
     let report3 = pds.compute_report(report_request3).unwrap();
@@ -131,6 +145,8 @@ fn main() {
         epoch_end: 3,
         attributable_value: 3.0,
         laplace_noise_scale: 1.0,
+        privacy_parameters: None,
+        querier_id: 1,
         is_relevant_event: |e: &SimpleEvent| e.event_key == 1,
     };
     let report4 = pds.compute_report(report_request4).unwrap();
@@ -141,3 +157,231 @@ fn main() {
 fn always_relevant_event(_: &SimpleEvent) -> bool {
     true
 }
+
+/// Epochs of 100 seconds.
+fn epoch_clock() -> EpochClock {
+    EpochClock::new(100).unwrap()
+}
+
+/// Event at the start of `epoch_number`.
+fn event(id: usize, epoch_number: usize, event_key: usize) -> SimpleEvent {
+    let clock = epoch_clock();
+    SimpleEvent::new(id, clock.get_epoch_start(epoch_number), event_key, &clock)
+}
+
+#[test]
+fn lookback_window() {
+    type Filters = HashMapFilterStorage<
+        FilterId<usize, usize>,
+        PureDPBudgetFilter,
+        PureDPBudget,
+    >;
+    let mut pds: EpochPrivateDataService<_, _, _, _, anyhow::Error> =
+        EpochPrivateDataService::new(
+            Filters::new(),
+            HashMapEventStorage::new(),
+            HashMapDeduplicationKeyStorage::new(),
+            epoch_clock(),
+            PureDPBudget::Epsilon(3.0),
+            PureDPBudget::Infinite,
+        );
+
+    // Epochs of 100 seconds, with events at 120 and 180 (epoch 1) and 230
+    // (epoch 2).
+    let clock = pds.epoch_clock;
+    for (id, timestamp) in [(1, 120), (2, 180), (3, 230)] {
+        let event = SimpleEvent::new(id, timestamp, id, &clock);
+        pds.register_event(event).unwrap();
+    }
+
+    // The requested epochs are the epochs that overlap with the window.
+    let request = |trigger_time, duration, attributable_value| {
+        let lookback_window = LookbackWindow {
+            trigger_time,
+            duration,
+        };
+        AttributionHistogramRequest {
+            epoch_ids: vec![],
+            attributable_value,
+            noise_scale: NoiseScale::Laplace(100.0),
+            querier_id: 1,
+            model: AttributionModel::Linear,
+            relevant_event_selector: SimpleRelevantEventSelector {
+                lambda: always_relevant_event,
+            },
+            lookback_window: None,
+            bucket_key: |event: &SimpleEvent| event.event_key,
+            possible_bucket_keys: None,
+        }
+        .with_lookback_window(lookback_window, &clock)
+    };
+    let remaining = |filters: &Filters, epoch_id| {
+        filters
+            .get_remaining_budget(&FilterId::Querier(epoch_id, 1))
+            .unwrap()
+    };
+
+    // The window [160, 260] covers part of epoch 1: the event at 120 is not
+    // attributed. Both epochs pay with the global sensitivity, 2 * 100.
+    let report = pds.compute_report(request(260, 100, 100.0)).unwrap();
+    assert_eq!(report.bin_values, HashMap::from([(2, 50.0), (3, 50.0)]));
+    assert_eq!(
+        remaining(&pds.filter_storage, 1),
+        PureDPBudget::Epsilon(1.0)
+    );
+    assert_eq!(
+        remaining(&pds.filter_storage, 2),
+        PureDPBudget::Epsilon(1.0)
+    );
+
+    // The window [210, 260] overlaps with epoch 2 only.
+    let report = pds.compute_report(request(260, 50, 1.0)).unwrap();
+    assert_eq!(report.bin_values, HashMap::from([(3, 1.0)]));
+    assert_eq!(
+        remaining(&pds.filter_storage, 1),
+        PureDPBudget::Epsilon(1.0)
+    );
+    assert_eq!(
+        remaining(&pds.filter_storage, 2),
+        PureDPBudget::Epsilon(0.99)
+    );
+
+    // The window [130, 170] overlaps with epoch 1 only, but both events of
+    // epoch 1 are out of the window, so epoch 1 is not charged.
+    let report = pds.compute_report(request(170, 40, 1.0)).unwrap();
+    assert!(report.bin_values.is_empty());
+    assert_eq!(
+        remaining(&pds.filter_storage, 1),
+        PureDPBudget::Epsilon(1.0)
+    );
+}
+
+#[test]
+fn gaussian_noise() {
+    type Filters = HashMapFilterStorage<
+        FilterId<usize, usize>,
+        ZcdpBudgetFilter,
+        ZcdpBudget,
+    >;
+    let mut pds: EpochPrivateDataService<_, _, _, _, anyhow::Error> =
+        EpochPrivateDataService::new(
+            Filters::new(),
+            HashMapEventStorage::new(),
+            HashMapDeduplicationKeyStorage::new(),
+            epoch_clock(),
+            ZcdpBudget::Rho(10.0),
+            ZcdpBudget::Infinite,
+        );
+
+    // Four events with different keys in the same epoch.
+    for id in 1..=4 {
+        let event = event(id, 1, id);
+        pds.register_event(event).unwrap();
+    }
+
+    let request = |noise_scale| AttributionHistogramRequest {
+        epoch_ids: vec![1],
+        attributable_value: 4.0,
+        noise_scale,
+        querier_id: 1,
+        model: AttributionModel::Linear,
+        relevant_event_selector: SimpleRelevantEventSelector {
+            lambda: always_relevant_event,
+        },
+        lookback_window: None,
+        bucket_key: |event: &SimpleEvent| event.event_key,
+        possible_bucket_keys: None,
+    };
+    let remaining = |filters: &Filters| {
+        filters
+            .get_remaining_budget(&FilterId::Querier(1, 1))
+            .unwrap()
+    };
+
+    // The report has 4 bins with value 1, i.e. an L2 norm of 2. With sigma
+    // = 2, the loss is rho = 2^2 / (2 * 2^2) = 1/2.
+    let report = pds
+        .compute_report(request(NoiseScale::Gaussian(2.0)))
+        .unwrap();
+    assert_eq!(report.bin_values.len(), 4);
+    assert_eq!(remaining(&pds.filter_storage), ZcdpBudget::Rho(9.5));
+
+    // Laplace noise with the same scale is charged the L1 norm 4, i.e.
+    // epsilon = 2 and rho = 2.
+    pds.compute_report(request(NoiseScale::Laplace(2.0)))
+        .unwrap();
+    assert_eq!(remaining(&pds.filter_storage), ZcdpBudget::Rho(7.5));
+}
+
+#[test]
+fn single_bucket_sensitivity() {
+    type Filters = HashMapFilterStorage<
+        FilterId<usize, usize>,
+        PureDPBudgetFilter,
+        PureDPBudget,
+    >;
+    let mut pds: EpochPrivateDataService<_, _, _, _, anyhow::Error> =
+        EpochPrivateDataService::new(
+            Filters::new(),
+            HashMapEventStorage::new(),
+            HashMapDeduplicationKeyStorage::new(),
+            epoch_clock(),
+            PureDPBudget::Epsilon(3.0),
+            PureDPBudget::Infinite,
+        );
+
+    // One event with the same key in each of epochs 1 and 2, so both epochs
+    // pay with the global sensitivity.
+    for epoch_number in [1, 2] {
+        let event = event(epoch_number, epoch_number, 7);
+        pds.register_event(event).unwrap();
+    }
+
+    let request =
+        |querier_id, possible_bucket_keys| AttributionHistogramRequest {
+            epoch_ids: vec![2, 1],
+            attributable_value: 1.0,
+            noise_scale: NoiseScale::Laplace(1.0),
+            querier_id,
+            model: AttributionModel::LastTouch,
+            relevant_event_selector: SimpleRelevantEventSelector {
+                lambda: always_relevant_event,
+            },
+            lookback_window: None,
+            bucket_key: |event: &SimpleEvent| event.event_key,
+            possible_bucket_keys,
+        };
+    let remaining = |filters: &Filters, epoch_id, querier_id| {
+        filters
+            .get_remaining_budget(&FilterId::Querier(epoch_id, querier_id))
+            .unwrap()
+    };
+
+    // Without declared buckets, the global sensitivity is 2 * 1.
+    let report = pds.compute_report(request(1, None)).unwrap();
+    assert_eq!(report.bin_values, HashMap::from([(7, 1.0)]));
+    for epoch_id in [1, 2] {
+        assert_eq!(
+            remaining(&pds.filter_storage, epoch_id, 1),
+            PureDPBudget::Epsilon(1.0)
+        );
+    }
+
+    // With a single possible bucket, it is only 1.
+    let report = pds
+        .compute_report(request(2, Some(HashSet::from([7]))))
+        .unwrap();
+    assert_eq!(report.bin_values, HashMap::from([(7, 1.0)]));
+    for epoch_id in [1, 2] {
+        assert_eq!(
+            remaining(&pds.filter_storage, epoch_id, 2),
+            PureDPBudget::Epsilon(2.0)
+        );
+    }
+
+    // Values attributed to undeclared buckets are dropped.
+    let report = pds
+        .compute_report(request(3, Some(HashSet::from([8]))))
+        .unwrap();
+    assert!(report.bin_values.is_empty());
+}